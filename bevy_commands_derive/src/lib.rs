@@ -3,7 +3,13 @@
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    DeriveInput, Token,
+};
 
 #[proc_macro_derive(AppCommand, attributes(command))]
 pub fn derive_command(input: TokenStream) -> TokenStream {
@@ -28,27 +34,48 @@ fn get_command_name(input: &DeriveInput) -> syn::LitStr {
     input
         .attrs
         .iter()
-        .find_map(|attr| {
-            if !attr.path.is_ident("command") {
-                return None;
-            };
-            let Ok(syn::Meta::List(list)) = attr.parse_meta() else {
-                return None;
-            };
-            list.nested.iter().find_map(|meta| {
-                if let syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) = meta {
-                    Some(nv.lit.clone())
-                } else {
-                    None
-                }
-            })
+        .filter(|attr| attr.path.is_ident("command"))
+        .filter_map(|attr| {
+            attr.parse_args_with(Punctuated::<CommandArg, Token![,]>::parse_terminated)
+                .ok()
         })
-        .map(|lit| {
-            if let syn::Lit::Str(str) = lit {
-                str
-            } else {
-                panic!("expected string literal as command name");
+        .flatten()
+        .find_map(|arg| {
+            if arg.key != "name" {
+                return None;
+            }
+            match arg.value {
+                Some(syn::Lit::Str(str)) => Some(str),
+                _ => panic!("expected string literal as command name"),
             }
         })
         .unwrap_or_else(|| syn::LitStr::new(&input.ident.to_string(), input.ident.span()))
 }
+
+/// A single `key` or `key = value` argument in a `#[command(...)]` attribute.
+///
+/// Values which aren't literals, such as `visible_aliases = ["a", "b"]`, are skipped over.
+struct CommandArg {
+    key: syn::Ident,
+    value: Option<syn::Lit>,
+}
+
+impl Parse for CommandArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = input.call(syn::Ident::parse_any)?;
+        let mut value = None;
+        if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            if input.peek(syn::Lit) {
+                let lit = input.parse()?;
+                if input.is_empty() || input.peek(Token![,]) {
+                    value = Some(lit);
+                }
+            }
+        }
+        while !input.is_empty() && !input.peek(Token![,]) {
+            input.parse::<proc_macro2::TokenTree>()?;
+        }
+        Ok(Self { key, value })
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{dispatch::PendingOutcomes, CommandBufInput, CommandCompleted, CommandFinished};
+use crate::{CommandId, Outcome};
+
+/// An operator separating two commands in a command buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChainOp {
+    /// `;`: always runs the next command.
+    Then,
+    /// `&&`: runs the next command only if the previous one succeeded.
+    And,
+    /// `||`: runs the next command only if the previous one failed.
+    Or,
+}
+
+impl ChainOp {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Then => ";",
+            Self::And => "&&",
+            Self::Or => "||",
+        }
+    }
+
+    /// Checks if the command after this operator should run, given the outcome of the last
+    /// command which ran.
+    pub fn should_run(self, last: Outcome) -> bool {
+        match self {
+            Self::Then => true,
+            Self::And => last == Outcome::Ok,
+            Self::Or => last == Outcome::Err,
+        }
+    }
+}
+
+/// A single command in a chain of commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainSegment<'a> {
+    /// The operator preceding this command, or [`None`] if this is the first command.
+    pub op: Option<ChainOp>,
+    /// Byte index of the start of this command in the buffer.
+    pub start: usize,
+    pub buf: &'a str,
+}
+
+/// Splits a command buffer on the `;`, `&&` and `||` operators which are not quoted or escaped.
+///
+/// Segments may be empty or only whitespace, such as the last segment of `echo a;`.
+pub fn split_chain(buf: &str) -> Vec<ChainSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut op = None;
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut chars = buf.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        let next_op = match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                escaped = true;
+                None
+            }
+            (c, Some(q)) => {
+                if c == q {
+                    quote = None;
+                }
+                None
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                None
+            }
+            (';', None) => Some((ChainOp::Then, 1)),
+            ('&', None) if chars.peek().map(|(_, c)| *c) == Some('&') => Some((ChainOp::And, 2)),
+            ('|', None) if chars.peek().map(|(_, c)| *c) == Some('|') => Some((ChainOp::Or, 2)),
+            _ => None,
+        };
+        if let Some((next_op, len)) = next_op {
+            segments.push(ChainSegment {
+                op,
+                start,
+                buf: &buf[start..i],
+            });
+            if len > 1 {
+                chars.next();
+            }
+            op = Some(next_op);
+            start = i + len;
+        }
+    }
+
+    segments.push(ChainSegment {
+        op,
+        start,
+        buf: &buf[start..],
+    });
+    segments
+}
+
+/// Checks that every `&&` and `||` in a chain has a command on both sides, and removes empty
+/// commands, which are allowed around `;`.
+pub(crate) fn validate_chain(
+    segments: Vec<ChainSegment<'_>>,
+) -> Result<Vec<ChainSegment<'_>>, String> {
+    for (i, segment) in segments.iter().enumerate() {
+        if !segment.buf.trim().is_empty() {
+            continue;
+        }
+        let next_op = segments.get(i + 1).and_then(|next| next.op);
+        for op in [segment.op, next_op].into_iter().flatten() {
+            if op != ChainOp::Then {
+                return Err(format!("Expected a command around '{}'", op.as_str()));
+            }
+        }
+    }
+    Ok(segments
+        .into_iter()
+        .filter(|segment| !segment.buf.trim().is_empty())
+        .collect())
+}
+
+/// The remaining commands of a chain, waiting for the currently running command to complete.
+pub(crate) struct CommandChain {
+    pub sender: Entity,
+    /// The ID of the input which the whole chain was sent as.
+    pub id: CommandId,
+    /// The user aliases which were expanded to produce the chain, which may not be expanded again
+    /// by its commands.
+    pub expanded: Vec<String>,
+    pub remaining: VecDeque<(ChainOp, String)>,
+}
+
+/// Chains of commands, keyed by the ID of the command in the chain which is currently running.
+///
+/// Each command in a chain is run with its own [`CommandId`], so responses to it are sent with
+/// that ID rather than the ID of the [`CommandBufInput`] which the chain was sent as. Use
+/// [`CommandChains::root_id`] to find the ID of the input.
+#[derive(Resource, Default)]
+pub struct CommandChains(pub(crate) HashMap<CommandId, CommandChain>);
+
+impl CommandChains {
+    /// Finds the ID of the input which started the chain that `id` is a part of, or `id` itself
+    /// if it is not part of a chain.
+    ///
+    /// A command is only known to be part of a chain until it completes, so responses should be
+    /// looked up before [`PostUpdate`], in the frame they are sent.
+    pub fn root_id(&self, mut id: CommandId) -> CommandId {
+        while let Some(chain) = self.0.get(&id) {
+            id = chain.id;
+        }
+        id
+    }
+}
+
+pub(crate) fn advance_command_chains(
+    mut completed: EventReader<CommandCompleted>,
+    mut chains: ResMut<CommandChains>,
+    mut pending: ResMut<PendingOutcomes>,
+    mut buf_input: EventWriter<CommandBufInput>,
+    mut finished: EventWriter<CommandFinished>,
+) {
+    for event in completed.iter() {
+        let Some(mut chain) = chains.0.remove(&event.id) else {
+            continue;
+        };
+        let next = loop {
+            match chain.remaining.pop_front() {
+                Some((op, buf)) if op.should_run(event.outcome) => break Some(buf),
+                Some(_) => continue,
+                None => break None,
+            }
+        };
+        match next {
+            Some(buf) => {
+                let input = CommandBufInput::new(chain.sender, buf);
+                chains.0.insert(input.id, chain);
+                buf_input.send(input);
+            }
+            None => {
+                pending.0.insert(chain.id, event.outcome);
+                finished.send(CommandFinished {
+                    target: chain.sender,
+                    id: chain.id,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(buf: &str) -> Vec<(Option<ChainOp>, &str)> {
+        split_chain(buf)
+            .into_iter()
+            .map(|segment| (segment.op, segment.buf))
+            .collect()
+    }
+
+    #[test]
+    fn split_operators() {
+        use ChainOp::*;
+        assert_eq!(split("echo a"), [(None, "echo a")]);
+        assert_eq!(
+            split("a; b && c || d"),
+            [
+                (None, "a"),
+                (Some(Then), " b "),
+                (Some(And), " c "),
+                (Some(Or), " d")
+            ]
+        );
+        assert_eq!(split("a;"), [(None, "a"), (Some(Then), "")]);
+        assert_eq!(split(""), [(None, "")]);
+    }
+
+    #[test]
+    fn split_ignores_quoted_and_escaped_operators() {
+        assert_eq!(
+            split(r#"echo "a; b" 'c && d'"#),
+            [(None, r#"echo "a; b" 'c && d'"#)]
+        );
+        assert_eq!(split(r"echo a\; b"), [(None, r"echo a\; b")]);
+        assert_eq!(split("echo a & b | c"), [(None, "echo a & b | c")]);
+    }
+
+    #[test]
+    fn split_segment_starts() {
+        let segments = split_chain("ab && cd");
+        assert_eq!(segments[0].start, 0);
+        assert_eq!(segments[1].start, 5);
+    }
+
+    #[test]
+    fn validate_allows_empty_commands_around_then() {
+        let bufs = |buf| {
+            validate_chain(split_chain(buf))
+                .unwrap()
+                .into_iter()
+                .map(|segment| segment.buf)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bufs("a;"), ["a"]);
+        assert_eq!(bufs("; a ;; b"), [" a ", " b"]);
+        assert!(bufs(" ; ").is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_missing_commands_around_and_or() {
+        for buf in ["a &&", "&& a", "a || ", "a && ; b", "a; || b"] {
+            assert!(validate_chain(split_chain(buf)).is_err(), "{:?}", buf);
+        }
+    }
+
+    #[test]
+    fn should_run() {
+        assert!(ChainOp::Then.should_run(Outcome::Err));
+        assert!(ChainOp::And.should_run(Outcome::Ok));
+        assert!(!ChainOp::And.should_run(Outcome::Err));
+        assert!(ChainOp::Or.should_run(Outcome::Err));
+        assert!(!ChainOp::Or.should_run(Outcome::Ok));
+    }
+}
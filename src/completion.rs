@@ -0,0 +1,466 @@
+use std::sync::{Arc, RwLock};
+
+use bevy::prelude::*;
+
+use crate::cvar::{CvarMeta, CvarMetaMap, CVAR_VALUE_NAME};
+use crate::{chain::split_chain, plugin::CommandAliases, CommandMetaMap, UserAliases};
+
+/// A snapshot of the registered commands and cvars, used to generate completions for a command
+/// line.
+///
+/// This is kept separately from [`CommandMetaMap`] so that it can be shared with threads which
+/// do not have access to the [`World`], such as the stdio input thread.
+#[derive(Clone, Default)]
+pub struct CompletionData {
+    commands: Vec<clap::Command>,
+    cvars: Vec<(String, CvarMeta)>,
+    user_aliases: Vec<String>,
+}
+
+/// Shared handle to the latest [`CompletionData`], kept up to date whenever commands are
+/// registered.
+#[derive(Resource, Clone, Default)]
+pub struct SharedCompletionData(pub Arc<RwLock<CompletionData>>);
+
+/// A single completion candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// The text which replaces the word being completed.
+    pub value: String,
+    /// A short description of what this candidate is.
+    pub help: Option<String>,
+}
+
+impl Completion {
+    /// Formats this candidate for display in a list of candidates, including its help text.
+    pub fn display(&self) -> String {
+        self.help.as_ref().map_or_else(
+            || self.value.clone(),
+            |help| format!("{}  ({})", self.value, help),
+        )
+    }
+}
+
+/// A whitespace-separated word in a command line, taking quotes into account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Byte index of the start of the token in the line.
+    pub start: usize,
+    /// Byte index of the end of the token in the line.
+    pub end: usize,
+    /// The contents of the token, with quotes and escapes removed.
+    pub value: String,
+}
+
+/// Splits a (possibly incomplete) command line into tokens, in the same way as [`shlex`] would
+/// split a complete line.
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        let end = i + c.len_utf8();
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote != Some('\'') {
+            escaped = true;
+            current.get_or_insert_with(|| new_token(i)).end = end;
+            continue;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+                current.get_or_insert_with(|| new_token(i)).end = end;
+                continue;
+            }
+        } else if c == '"' || c == '\'' {
+            quote = Some(c);
+            current.get_or_insert_with(|| new_token(i)).end = end;
+            continue;
+        } else if c.is_whitespace() {
+            tokens.extend(current.take());
+            continue;
+        }
+
+        let token = current.get_or_insert_with(|| new_token(i));
+        token.value.push(c);
+        token.end = end;
+    }
+
+    tokens.extend(current);
+    tokens
+}
+
+const fn new_token(start: usize) -> Token {
+    Token {
+        start,
+        end: start,
+        value: String::new(),
+    }
+}
+
+impl CompletionData {
+    pub fn from_meta(
+        command_meta: &CommandMetaMap,
+        aliases: &CommandAliases,
+        user_aliases: &UserAliases,
+        cvar_meta: &CvarMetaMap,
+    ) -> Self {
+        let mut commands = command_meta
+            .0
+            .iter()
+            .map(|(name, command)| {
+                let mut command = command
+                    .clone()
+                    .aliases(aliases.aliases_of(name).map(str::to_owned));
+                command.build();
+                command
+            })
+            .collect::<Vec<_>>();
+        commands.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+        let mut cvars = cvar_meta
+            .0
+            .iter()
+            .map(|(name, meta)| (name.clone(), meta.clone()))
+            .collect::<Vec<_>>();
+        cvars.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut user_aliases = user_aliases.0.keys().cloned().collect::<Vec<_>>();
+        user_aliases.sort();
+
+        Self {
+            commands,
+            cvars,
+            user_aliases,
+        }
+    }
+
+    pub fn commands(&self) -> &[clap::Command] {
+        &self.commands
+    }
+
+    pub fn cvars(&self) -> &[(String, CvarMeta)] {
+        &self.cvars
+    }
+
+    pub fn find_cvar(&self, name: &str) -> Option<&CvarMeta> {
+        self.cvars
+            .iter()
+            .find(|(cvar, _)| cvar == name)
+            .map(|(_, meta)| meta)
+    }
+
+    pub fn find_command(&self, name: &str) -> Option<&clap::Command> {
+        self.commands.iter().find(|command| {
+            command.get_name() == name || command.get_all_aliases().any(|a| a == name)
+        })
+    }
+
+    pub fn is_user_alias(&self, name: &str) -> bool {
+        self.user_aliases
+            .binary_search_by(|alias| alias.as_str().cmp(name))
+            .is_ok()
+    }
+
+    /// Generates completions for the word under the cursor at byte index `pos` in `line`.
+    ///
+    /// Returns the byte index at which the word being completed starts, and the candidates
+    /// which could replace it.
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Completion>) {
+        // only the last command in a chain is being typed
+        let (offset, line) = split_chain(&line[..pos])
+            .pop()
+            .map_or((0, &line[..pos]), |segment| (segment.start, segment.buf));
+        let mut tokens = tokenize(line);
+        let current = match tokens.last() {
+            Some(token) if token.end == line.len() => tokens.pop().unwrap(),
+            _ => new_token(line.len()),
+        };
+
+        let candidates = match tokens.split_first() {
+            None => self
+                .commands
+                .iter()
+                .filter(|command| !command.is_hide_set())
+                .map(|command| Completion {
+                    value: command.get_name().to_owned(),
+                    help: command.get_about().map(|s| s.to_string()),
+                })
+                .collect(),
+            Some((name, args)) => match self.find_command(&name.value) {
+                Some(command) => self.complete_args(command, args, &current.value),
+                None => Vec::new(),
+            },
+        };
+
+        let candidates = candidates
+            .into_iter()
+            .filter(|candidate| candidate.value.starts_with(&current.value))
+            .collect();
+        (offset + current.start, candidates)
+    }
+
+    fn complete_args(
+        &self,
+        mut command: &clap::Command,
+        args: &[Token],
+        current: &str,
+    ) -> Vec<Completion> {
+        let mut pending_value = None;
+        let mut positionals = Vec::new();
+        let mut only_positionals = false;
+        for arg in args {
+            if pending_value.take().is_some() {
+                continue;
+            }
+            if only_positionals {
+                positionals.push(arg.value.as_str());
+            } else if arg.value == "--" {
+                only_positionals = true;
+            } else if let Some(subcommand) = positionals
+                .is_empty()
+                .then(|| command.find_subcommand(&arg.value))
+                .flatten()
+            {
+                command = subcommand;
+            } else if let Some(flag) = find_flag(command, &arg.value) {
+                if flag.get_action().takes_values() && !arg.value.contains('=') {
+                    pending_value = Some(flag);
+                }
+            } else {
+                positionals.push(arg.value.as_str());
+            }
+        }
+
+        if let Some(flag) = pending_value {
+            return possible_values(flag);
+        }
+
+        if current.starts_with('-') && !only_positionals {
+            let mut candidates = Vec::new();
+            for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+                let help = arg.get_help().map(|s| s.to_string());
+                if let Some(long) = arg.get_long() {
+                    candidates.push(Completion {
+                        value: format!("--{}", long),
+                        help: help.clone(),
+                    });
+                }
+                if let Some(short) = arg.get_short().filter(|_| !current.starts_with("--")) {
+                    candidates.push(Completion {
+                        value: format!("-{}", short),
+                        help,
+                    });
+                }
+            }
+            return candidates;
+        }
+
+        let mut candidates = Vec::new();
+        if positionals.is_empty() {
+            candidates.extend(
+                command
+                    .get_subcommands()
+                    .filter(|subcommand| !subcommand.is_hide_set())
+                    .map(|subcommand| Completion {
+                        value: subcommand.get_name().to_owned(),
+                        help: subcommand.get_about().map(|s| s.to_string()),
+                    }),
+            );
+        }
+
+        let index = positionals.len();
+        let cvar_arg = |i: usize| {
+            command
+                .get_positionals()
+                .nth(i)
+                .and_then(|arg| arg.get_value_names())
+                .is_some_and(|names| names.iter().any(|name| name == CVAR_VALUE_NAME))
+        };
+        if cvar_arg(index) {
+            candidates.extend(self.cvars.iter().map(|(name, meta)| Completion {
+                value: name.clone(),
+                help: meta.description.clone(),
+            }));
+        } else if let Some(meta) = index
+            .checked_sub(1)
+            .filter(|i| cvar_arg(*i))
+            .and_then(|i| self.find_cvar(positionals[i]))
+        {
+            candidates.extend(meta.possible_values.iter().map(|value| Completion {
+                value: value.clone(),
+                help: None,
+            }));
+        } else if let Some(arg) = command.get_positionals().nth(index) {
+            candidates.extend(possible_values(arg));
+        }
+        candidates
+    }
+}
+
+pub(crate) fn find_flag<'a>(command: &'a clap::Command, arg: &str) -> Option<&'a clap::Arg> {
+    if let Some(long) = arg.strip_prefix("--") {
+        let long = long.split('=').next().unwrap_or(long);
+        command.get_arguments().find(|flag| {
+            flag.get_long() == Some(long)
+                || flag
+                    .get_all_aliases()
+                    .is_some_and(|aliases| aliases.contains(&long))
+        })
+    } else if let Some(short) = arg.strip_prefix('-') {
+        let mut chars = short.chars();
+        let (Some(short), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        command
+            .get_arguments()
+            .find(|flag| flag.get_short() == Some(short))
+    } else {
+        None
+    }
+}
+
+fn possible_values(arg: &clap::Arg) -> Vec<Completion> {
+    arg.get_possible_values()
+        .into_iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| Completion {
+            value: value.get_name().to_owned(),
+            help: value.get_help().map(|s| s.to_string()),
+        })
+        .collect()
+}
+
+pub(crate) fn update_completion_data(
+    command_meta: Res<CommandMetaMap>,
+    aliases: Res<CommandAliases>,
+    user_aliases: Res<UserAliases>,
+    cvar_meta: Res<CvarMetaMap>,
+    completions: Res<SharedCompletionData>,
+) {
+    let data = CompletionData::from_meta(&command_meta, &aliases, &user_aliases, &cvar_meta);
+    match completions.0.write() {
+        Ok(mut completions) => *completions = data,
+        Err(e) => warn!("Could not update completion data: {}", e),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bevy::utils::HashMap;
+    use clap::{Arg, ArgAction, Command};
+
+    use super::*;
+
+    /// Completion data for `echo`, `log` and `set` commands, the `say` alias of `echo`, the `hi`
+    /// user alias, and the `gravity` and `difficulty` cvars.
+    pub fn data() -> CompletionData {
+        let mut command_meta = CommandMetaMap(HashMap::default());
+        let mut insert = |name: &'static str, command: Command| {
+            // as done when registering a command
+            command_meta.0.insert(name, command.no_binary_name(true));
+        };
+        insert(
+            "echo",
+            Command::new("echo").about("Prints a message").arg(
+                Arg::new("message")
+                    .help("The message to print")
+                    .required(true),
+            ),
+        );
+        insert(
+            "log",
+            Command::new("log")
+                .arg(
+                    Arg::new("level")
+                        .long("level")
+                        .short('l')
+                        .help("The level to log at")
+                        .value_parser(["info", "warn"]),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .action(ArgAction::SetTrue),
+                ),
+        );
+        insert(
+            "set",
+            Command::new("set")
+                .arg(Arg::new("cvar").value_name(CVAR_VALUE_NAME).required(true))
+                .arg(Arg::new("value").required(true)),
+        );
+        let aliases = CommandAliases(HashMap::from_iter([("say".to_owned(), "echo")]));
+        let user_aliases = UserAliases(HashMap::from_iter([(
+            "hi".to_owned(),
+            "echo hi".to_owned(),
+        )]));
+        let cvar = |value: &str, possible_values: &[&str]| CvarMeta {
+            description: None,
+            default_value: value.to_owned(),
+            value: value.to_owned(),
+            possible_values: possible_values.iter().map(|s| (*s).to_owned()).collect(),
+            archive: false,
+        };
+        let cvar_meta = CvarMetaMap(HashMap::from_iter([
+            ("gravity".to_owned(), cvar("9.8", &[])),
+            ("difficulty".to_owned(), cvar("easy", &["easy", "hard"])),
+        ]));
+        CompletionData::from_meta(&command_meta, &aliases, &user_aliases, &cvar_meta)
+    }
+
+    fn complete(line: &str) -> (usize, Vec<String>) {
+        let (start, candidates) = data().complete(line, line.len());
+        (start, candidates.into_iter().map(|c| c.value).collect())
+    }
+
+    #[test]
+    fn tokenize_quotes() {
+        let tokens = tokenize(r#"echo "a b" c\ d"#);
+        let values = tokens.iter().map(|t| t.value.as_str()).collect::<Vec<_>>();
+        assert_eq!(values, ["echo", "a b", "c d"]);
+        assert_eq!((tokens[1].start, tokens[1].end), (5, 10));
+    }
+
+    #[test]
+    fn complete_command_names() {
+        assert_eq!(
+            complete(""),
+            (0, vec!["echo".into(), "log".into(), "set".into()])
+        );
+        assert_eq!(complete("ec"), (0, vec!["echo".into()]));
+        assert_eq!(complete("  l"), (2, vec!["log".into()]));
+    }
+
+    #[test]
+    fn complete_flags_and_values() {
+        assert_eq!(complete("log --v"), (4, vec!["--verbose".into()]));
+        assert_eq!(complete("log -l "), (7, vec!["info".into(), "warn".into()]));
+        assert_eq!(complete("log --level w"), (12, vec!["warn".into()]));
+        // aliases complete the arguments of the command they refer to
+        assert_eq!(complete("say -"), (4, vec!["--help".into(), "-h".into()]));
+    }
+
+    #[test]
+    fn complete_cvars() {
+        assert_eq!(complete("set gr"), (4, vec!["gravity".into()]));
+        assert_eq!(
+            complete("set difficulty "),
+            (15, vec!["easy".into(), "hard".into()])
+        );
+        assert_eq!(complete("set gravity "), (12, vec![]));
+    }
+
+    #[test]
+    fn complete_last_command_of_chain() {
+        assert_eq!(complete("echo a; se"), (8, vec!["set".into()]));
+        assert_eq!(complete("echo a && log --l"), (14, vec!["--level".into()]));
+    }
+
+    #[test]
+    fn complete_unknown_command() {
+        assert_eq!(complete("nope "), (5, vec![]));
+    }
+}
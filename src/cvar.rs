@@ -0,0 +1,419 @@
+use std::fmt;
+
+use bevy::reflect::serde::{TypedReflectDeserializer, TypedReflectSerializer};
+use bevy::reflect::{
+    GetPath, GetTypeRegistration, ReflectRef, TypeInfo, TypeRegistryInternal, VariantInfo,
+};
+use bevy::{prelude::*, utils::HashMap};
+use serde::de::DeserializeSeed;
+
+use crate::dispatch::forward_deferred_responses;
+use crate::{respond_ok, CommandResponder, CommandSet, ResponseHandle};
+
+/// Value name of command arguments which take the name of a cvar.
+///
+/// Arguments with this value name are completed with the names of registered cvars.
+pub const CVAR_VALUE_NAME: &str = "CVAR";
+
+/// A console variable: a named, typed value stored as a resource, which can be read and written
+/// from the console.
+///
+/// Cvars are usually defined using the [`cvar!`](crate::cvar!) macro, and registered using
+/// [`AddCvar::add_cvar`].
+pub trait Cvar: Resource {
+    type Value: Clone + fmt::Display + Send + Sync + 'static;
+
+    fn name() -> &'static str;
+
+    fn description() -> Option<&'static str> {
+        None
+    }
+
+    /// Whether the value of this cvar is saved between runs, when the
+    /// [`PersistPlugin`](crate::persist::PersistPlugin) is added.
+    fn archive() -> bool {
+        false
+    }
+
+    /// Parser used to convert console input into a value, which also determines the possible
+    /// values of the cvar.
+    fn value_parser() -> clap::builder::ValueParser;
+
+    fn default_value() -> Self::Value;
+
+    fn get(&self) -> &Self::Value;
+
+    fn set(&mut self, value: Self::Value);
+}
+
+/// Information on a registered cvar, used for listing and completing cvars.
+#[derive(Debug, Clone)]
+pub struct CvarMeta {
+    pub description: Option<String>,
+    pub default_value: String,
+    pub value: String,
+    pub possible_values: Vec<String>,
+    pub archive: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct CvarMetaMap(pub HashMap<String, CvarMeta>);
+
+/// Sent when the value of a cvar changes, either from the console or from a system.
+#[derive(Event)]
+pub struct CvarChanged {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum CvarOp {
+    Set(String),
+    Reset,
+}
+
+/// Request to change the value of a cvar, answered through `handle` by the system which owns
+/// the cvar.
+#[derive(Event)]
+pub struct CvarRequest {
+    pub handle: ResponseHandle,
+    pub name: String,
+    pub op: CvarOp,
+}
+
+pub trait AddCvar {
+    fn add_cvar<C: Cvar + FromWorld>(&mut self) -> &mut Self;
+
+    /// Registers every field of a reflected resource as a cvar, named by its path under
+    /// `prefix`, such as `physics.gravity.y`.
+    ///
+    /// Values are parsed as RON using the type registry. Fields of types which are not
+    /// registered can be listed, but not set. These cvars are not archived.
+    fn add_reflect_cvars<R>(&mut self, prefix: impl Into<String>) -> &mut Self
+    where
+        R: Resource + Reflect + GetTypeRegistration + FromWorld;
+}
+
+impl AddCvar for App {
+    fn add_cvar<C: Cvar + FromWorld>(&mut self) -> &mut Self {
+        let setup_cvar_meta = |cvar: Res<C>, mut cvar_meta: ResMut<CvarMetaMap>| {
+            let arg = value_arg::<C>();
+            let meta = CvarMeta {
+                description: C::description().map(str::to_owned),
+                default_value: C::default_value().to_string(),
+                value: cvar.get().to_string(),
+                possible_values: arg
+                    .get_possible_values()
+                    .iter()
+                    .filter(|value| !value.is_hide_set())
+                    .map(|value| value.get_name().to_owned())
+                    .collect(),
+                archive: C::archive(),
+            };
+            if cvar_meta.0.insert(C::name().to_owned(), meta).is_some() {
+                warn!("Cvar '{}' already exists, overwriting", C::name());
+            }
+        };
+
+        self.init_resource::<C>()
+            .add_systems(Startup, setup_cvar_meta)
+            .add_systems(
+                Update,
+                (handle_cvar_requests::<C>, sync_cvar_meta::<C>)
+                    .chain()
+                    .after(CommandSet::Process)
+                    .before(forward_deferred_responses),
+            )
+    }
+
+    fn add_reflect_cvars<R>(&mut self, prefix: impl Into<String>) -> &mut Self
+    where
+        R: Resource + Reflect + GetTypeRegistration + FromWorld,
+    {
+        let prefix = prefix.into();
+
+        let setup_prefix = prefix.clone();
+        let setup_cvar_meta = move |world: &mut World| {
+            let default = R::from_world(world);
+            let registry = world.resource::<AppTypeRegistry>().clone();
+            let registry = registry.read();
+            let current = world.resource::<R>();
+
+            let mut paths = Vec::new();
+            collect_field_paths(current, String::new(), &mut paths);
+            let metas = paths
+                .into_iter()
+                .filter_map(|path| {
+                    let default_field = reflect_field(&default, &path).ok()?;
+                    let field = reflect_field(current, &path).ok()?;
+                    let meta = CvarMeta {
+                        description: Some(bevy::utils::get_short_name(field.type_name())),
+                        default_value: reflect_to_string(default_field, &registry),
+                        value: reflect_to_string(field, &registry),
+                        possible_values: unit_variants(field),
+                        archive: false,
+                    };
+                    Some((reflect_cvar_name(&setup_prefix, &path), meta))
+                })
+                .collect::<Vec<_>>();
+
+            let mut cvar_meta = world.resource_mut::<CvarMetaMap>();
+            for (name, meta) in metas {
+                if cvar_meta.0.insert(name.clone(), meta).is_some() {
+                    warn!("Cvar '{}' already exists, overwriting", name);
+                }
+            }
+        };
+
+        let request_prefix = prefix.clone();
+        let handle_cvar_requests =
+            move |mut requests: EventReader<CvarRequest>,
+                  mut resource: ResMut<R>,
+                  registry: Res<AppTypeRegistry>,
+                  cvar_meta: Res<CvarMetaMap>| {
+                let registry = registry.read();
+                for request in requests.iter() {
+                    let Some(path) = reflect_field_path(&request_prefix, &request.name) else {
+                        continue;
+                    };
+                    let mut handle = request.handle.clone();
+                    let input = match &request.op {
+                        CvarOp::Set(input) => input.clone(),
+                        CvarOp::Reset => match cvar_meta.0.get(&request.name) {
+                            Some(meta) => meta.default_value.clone(),
+                            None => continue,
+                        },
+                    };
+
+                    let result = reflect_field_mut(resource.bypass_change_detection(), path)
+                        .and_then(|field| {
+                            apply_reflect_value(field, &input, &registry)?;
+                            Ok(reflect_to_string(field, &registry))
+                        });
+                    match result {
+                        Ok(value) => {
+                            resource.set_changed();
+                            respond_ok!(handle, "{} = {}", request.name, value);
+                        }
+                        Err(e) => handle.err(e),
+                    }
+                    handle.finish();
+                }
+            };
+
+        let sync_prefix = prefix;
+        let sync_cvar_meta =
+            move |resource: Res<R>,
+                  registry: Res<AppTypeRegistry>,
+                  mut cvar_meta: ResMut<CvarMetaMap>,
+                  mut changed: EventWriter<CvarChanged>| {
+                if !resource.is_changed() {
+                    return;
+                }
+                for (name, meta) in cvar_meta.bypass_change_detection().0.iter_mut() {
+                    let Some(path) = reflect_field_path(&sync_prefix, name) else {
+                        continue;
+                    };
+                    let Ok(field) = reflect_field(resource.as_ref(), path) else {
+                        continue;
+                    };
+                    let value = reflect_to_string(field, &registry.read());
+                    if meta.value != value {
+                        meta.value = value.clone();
+                        changed.send(CvarChanged {
+                            name: name.clone(),
+                            value,
+                        });
+                    }
+                }
+            };
+
+        self.init_resource::<R>()
+            .register_type::<R>()
+            .add_systems(Startup, setup_cvar_meta)
+            .add_systems(
+                Update,
+                (handle_cvar_requests, sync_cvar_meta)
+                    .chain()
+                    .after(CommandSet::Process)
+                    .before(forward_deferred_responses),
+            )
+    }
+}
+
+fn reflect_cvar_name(prefix: &str, path: &str) -> String {
+    if path.is_empty() {
+        prefix.to_owned()
+    } else {
+        format!("{}.{}", prefix, path)
+    }
+}
+
+fn reflect_field_path<'a>(prefix: &str, name: &'a str) -> Option<&'a str> {
+    match name.strip_prefix(prefix)? {
+        "" => Some(""),
+        rest => rest.strip_prefix('.'),
+    }
+}
+
+fn reflect_field<'a>(value: &'a dyn Reflect, path: &str) -> Result<&'a dyn Reflect, String> {
+    if path.is_empty() {
+        return Ok(value);
+    }
+    value.reflect_path(path).map_err(|e| e.to_string())
+}
+
+fn reflect_field_mut<'a>(
+    value: &'a mut dyn Reflect,
+    path: &str,
+) -> Result<&'a mut dyn Reflect, String> {
+    if path.is_empty() {
+        return Ok(value);
+    }
+    value.reflect_path_mut(path).map_err(|e| e.to_string())
+}
+
+/// Collects the paths of all fields which are not themselves structs or tuples.
+fn collect_field_paths(value: &dyn Reflect, path: String, paths: &mut Vec<String>) {
+    let join = |field: &dyn std::fmt::Display| {
+        if path.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", path, field)
+        }
+    };
+    match value.reflect_ref() {
+        ReflectRef::Struct(value) => {
+            for i in 0..value.field_len() {
+                if let (Some(name), Some(field)) = (value.name_at(i), value.field_at(i)) {
+                    collect_field_paths(field, join(&name), paths);
+                }
+            }
+        }
+        ReflectRef::TupleStruct(value) => {
+            for (i, field) in value.iter_fields().enumerate() {
+                collect_field_paths(field, join(&i), paths);
+            }
+        }
+        ReflectRef::Tuple(value) => {
+            for (i, field) in value.iter_fields().enumerate() {
+                collect_field_paths(field, join(&i), paths);
+            }
+        }
+        _ => paths.push(path),
+    }
+}
+
+fn unit_variants(value: &dyn Reflect) -> Vec<String> {
+    let Some(TypeInfo::Enum(info)) = value.get_represented_type_info() else {
+        return Vec::new();
+    };
+    info.iter()
+        .filter_map(|variant| match variant {
+            VariantInfo::Unit(variant) => Some(variant.name().to_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn reflect_to_string(value: &dyn Reflect, registry: &TypeRegistryInternal) -> String {
+    ron::to_string(&TypedReflectSerializer::new(value, registry))
+        .unwrap_or_else(|_| format!("{:?}", value))
+}
+
+/// Parses `input` as RON into the type of `target`, and applies it to `target`.
+///
+/// String fields also accept input which is not quoted.
+fn apply_reflect_value(
+    target: &mut dyn Reflect,
+    input: &str,
+    registry: &TypeRegistryInternal,
+) -> Result<(), String> {
+    let registration = registry
+        .get(target.as_any().type_id())
+        .ok_or_else(|| format!("Type {} is not registered", target.type_name()))?;
+    let parsed = ron::Deserializer::from_str(input)
+        .map_err(|e| e.to_string())
+        .and_then(|mut deserializer| {
+            TypedReflectDeserializer::new(registration, registry)
+                .deserialize(&mut deserializer)
+                .map_err(|e| e.to_string())
+        });
+    match parsed {
+        Ok(value) => target.apply(value.as_ref()),
+        Err(_) if target.is::<String>() => target.apply(&input.to_owned()),
+        Err(e) => return Err(format!("Invalid value '{}': {}", input, e)),
+    }
+    Ok(())
+}
+
+fn value_arg<C: Cvar>() -> clap::Arg {
+    clap::Arg::new("value")
+        .value_parser(C::value_parser())
+        .allow_hyphen_values(true)
+        .required(true)
+}
+
+/// Parses console input into a value of the cvar.
+pub fn parse_cvar_value<C: Cvar>(input: &str) -> Result<C::Value, String> {
+    clap::Command::new(C::name())
+        .no_binary_name(true)
+        .disable_help_flag(true)
+        .arg(value_arg::<C>())
+        .try_get_matches_from([input])
+        .map_err(|e| {
+            e.render()
+                .to_string()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+                .to_owned()
+        })
+        .and_then(|mut matches| {
+            matches
+                .remove_one::<C::Value>("value")
+                .ok_or_else(|| format!("No value provided for {}", C::name()))
+        })
+}
+
+fn handle_cvar_requests<C: Cvar>(mut requests: EventReader<CvarRequest>, mut cvar: ResMut<C>) {
+    for request in requests.iter().filter(|r| r.name == C::name()) {
+        let mut handle = request.handle.clone();
+        match &request.op {
+            CvarOp::Set(input) => match parse_cvar_value::<C>(input) {
+                Ok(value) => {
+                    cvar.set(value);
+                    respond_ok!(handle, "{} = {}", C::name(), cvar.get());
+                }
+                Err(e) => handle.err(e),
+            },
+            CvarOp::Reset => {
+                cvar.set(C::default_value());
+                respond_ok!(handle, "{} = {}", C::name(), cvar.get());
+            }
+        }
+        handle.finish();
+    }
+}
+
+fn sync_cvar_meta<C: Cvar>(
+    cvar: Res<C>,
+    mut cvar_meta: ResMut<CvarMetaMap>,
+    mut changed: EventWriter<CvarChanged>,
+) {
+    if !cvar.is_changed() {
+        return;
+    }
+    let value = cvar.get().to_string();
+    let Some(meta) = cvar_meta.bypass_change_detection().0.get_mut(C::name()) else {
+        return;
+    };
+    if meta.value != value {
+        meta.value = value.clone();
+        changed.send(CvarChanged {
+            name: C::name().to_owned(),
+            value,
+        });
+    }
+}
@@ -1,17 +1,63 @@
-use bevy::{ecs::system::SystemParam, prelude::*};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
 use clap::{CommandFactory, FromArgMatches};
 use expedition::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::plugin::InvalidCommandInput;
 
 pub trait AppCommand: Send + Sync + CommandFactory + FromArgMatches + 'static {
     fn name() -> &'static str;
 }
 
+/// Uniquely identifies a single invocation of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CommandId(pub u64);
+
+impl CommandId {
+    /// Allocates a new, unique ID.
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for CommandId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
 #[derive(Event)]
 pub struct CommandDispatch<C> {
     pub sender: Entity,
+    pub id: CommandId,
     pub data: C,
 }
 
+/// Sent when a command invocation has finished sending all of its responses.
+#[derive(Event)]
+pub struct CommandFinished {
+    pub target: Entity,
+    pub id: CommandId,
+}
+
+/// Sent once per invocation, after it has finished, with its final outcome.
+///
+/// The outcome is [`Outcome::Err`] if any of the invocation's responses was an error.
+#[derive(Event)]
+pub struct CommandCompleted {
+    pub target: Entity,
+    pub id: CommandId,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Outcome {
     Ok,
     Err,
@@ -20,22 +66,25 @@ pub enum Outcome {
 #[derive(Event)]
 pub struct CommandResponse {
     pub target: Entity,
+    pub id: CommandId,
     pub message: Message,
     pub outcome: Outcome,
 }
 
 impl CommandResponse {
-    pub fn ok(target: Entity, message: impl Into<Message>) -> Self {
+    pub fn ok(target: Entity, id: CommandId, message: impl Into<Message>) -> Self {
         Self {
             target,
+            id,
             message: message.into(),
             outcome: Outcome::Ok,
         }
     }
 
-    pub fn err(target: Entity, message: impl Into<Message>) -> Self {
+    pub fn err(target: Entity, id: CommandId, message: impl Into<Message>) -> Self {
         Self {
             target,
+            id,
             message: message.into(),
             outcome: Outcome::Err,
         }
@@ -46,6 +95,8 @@ impl CommandResponse {
 pub struct QueuedCommands<'w, 's, C: AppCommand> {
     commands: EventReader<'w, 's, CommandDispatch<C>>,
     responses: EventWriter<'w, CommandResponse>,
+    finished: EventWriter<'w, CommandFinished>,
+    deferred: Res<'w, DeferredResponses>,
 }
 
 impl<C: AppCommand> QueuedCommands<'_, '_, C> {
@@ -54,11 +105,21 @@ impl<C: AppCommand> QueuedCommands<'_, '_, C> {
         F: FnMut(CommandContext<C>),
     {
         for event in &mut self.commands {
+            let mut deferred = false;
             consume(CommandContext {
                 sender: event.sender,
+                id: event.id,
                 data: &event.data,
                 responses: &mut self.responses,
-            })
+                deferred_responses: &self.deferred,
+                deferred: &mut deferred,
+            });
+            if !deferred {
+                self.finished.send(CommandFinished {
+                    target: event.sender,
+                    id: event.id,
+                });
+            }
         }
     }
 }
@@ -77,16 +138,186 @@ pub trait CommandResponder {
 
 pub struct CommandContext<'a, 'w, C: AppCommand> {
     pub sender: Entity,
+    pub id: CommandId,
     pub data: &'a C,
     responses: &'a mut EventWriter<'w, CommandResponse>,
+    deferred_responses: &'a DeferredResponses,
+    deferred: &'a mut bool,
+}
+
+impl<C: AppCommand> CommandContext<'_, '_, C> {
+    /// Detaches the responses of this invocation from the current system run.
+    ///
+    /// The returned handle can be stored or moved into a task, and used to respond to the
+    /// sender later. The invocation is not considered finished until
+    /// [`ResponseHandle::finish`] is called, or every clone of the handle is dropped.
+    pub fn defer(self) -> ResponseHandle {
+        *self.deferred = true;
+        self.deferred_responses.handle(self.sender, self.id)
+    }
 }
 
 impl<C: AppCommand> CommandResponder for CommandContext<'_, '_, C> {
     fn respond(&mut self, outcome: Outcome, message: impl Into<Message>) {
         self.responses.send(CommandResponse {
             target: self.sender,
+            id: self.id,
+            message: message.into(),
+            outcome,
+        });
+    }
+}
+
+enum DeferredEvent {
+    Response(CommandResponse),
+    Finished(CommandFinished),
+}
+
+/// Channel through which [`ResponseHandle`]s send their responses back to the app.
+#[derive(Resource)]
+pub struct DeferredResponses {
+    tx: Sender<DeferredEvent>,
+    rx: Mutex<Receiver<DeferredEvent>>,
+}
+
+impl DeferredResponses {
+    /// Creates a handle for responding to an invocation which did not come from a command
+    /// system, such as a [`CvarRequest`](crate::cvar::CvarRequest) sent by the app itself.
+    pub fn handle(&self, sender: Entity, id: CommandId) -> ResponseHandle {
+        ResponseHandle {
+            sender,
+            id,
+            tx: self.tx.clone(),
+            finish: Arc::new(FinishGuard {
+                sender,
+                id,
+                tx: self.tx.clone(),
+                finished: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+impl Default for DeferredResponses {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx,
+            rx: Mutex::new(rx),
+        }
+    }
+}
+
+/// Handle for responding to a command invocation outside of the system which processed it.
+///
+/// Created using [`CommandContext::defer`]. Clones of a handle respond to the same invocation,
+/// which finishes when [`ResponseHandle::finish`] is first called on any of them, or once they
+/// have all been dropped.
+#[derive(Clone)]
+pub struct ResponseHandle {
+    pub sender: Entity,
+    pub id: CommandId,
+    tx: Sender<DeferredEvent>,
+    finish: Arc<FinishGuard>,
+}
+
+impl ResponseHandle {
+    /// Marks the invocation as finished. Responses sent through other clones of this handle
+    /// afterwards are still delivered, but the invocation is not finished again.
+    pub fn finish(self) {
+        self.finish.finish();
+    }
+
+    fn send(&self, event: DeferredEvent) {
+        if self.tx.send(event).is_err() {
+            warn!("Could not send deferred response for command {}", self.id);
+        }
+    }
+}
+
+/// Shared by the clones of a [`ResponseHandle`], so that their invocation is finished exactly
+/// once.
+struct FinishGuard {
+    sender: Entity,
+    id: CommandId,
+    tx: Sender<DeferredEvent>,
+    finished: AtomicBool,
+}
+
+impl FinishGuard {
+    fn finish(&self) {
+        if self.finished.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let event = DeferredEvent::Finished(CommandFinished {
+            target: self.sender,
+            id: self.id,
+        });
+        // the app may already have exited if the last handle is dropped late
+        let _ = self.tx.send(event);
+    }
+}
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl CommandResponder for ResponseHandle {
+    fn respond(&mut self, outcome: Outcome, message: impl Into<Message>) {
+        self.send(DeferredEvent::Response(CommandResponse {
+            target: self.sender,
+            id: self.id,
             message: message.into(),
             outcome,
+        }));
+    }
+}
+
+pub(crate) fn forward_deferred_responses(
+    deferred: Res<DeferredResponses>,
+    mut resps: EventWriter<CommandResponse>,
+    mut finished: EventWriter<CommandFinished>,
+) {
+    let Ok(rx) = deferred.rx.lock() else {
+        return;
+    };
+    for event in rx.try_iter() {
+        match event {
+            DeferredEvent::Response(resp) => resps.send(resp),
+            DeferredEvent::Finished(event) => finished.send(event),
+        }
+    }
+}
+
+/// Outcomes so far of the invocations which have responded but not yet finished.
+#[derive(Resource, Default)]
+pub(crate) struct PendingOutcomes(pub HashMap<CommandId, Outcome>);
+
+pub(crate) fn complete_commands(
+    mut resps: EventReader<CommandResponse>,
+    mut invalid: EventReader<InvalidCommandInput>,
+    mut finished: EventReader<CommandFinished>,
+    mut pending: ResMut<PendingOutcomes>,
+    mut completed: EventWriter<CommandCompleted>,
+) {
+    for resp in resps.iter() {
+        let outcome = pending.0.entry(resp.id).or_insert(Outcome::Ok);
+        if resp.outcome == Outcome::Err {
+            *outcome = Outcome::Err;
+        }
+    }
+    for event in invalid.iter() {
+        pending.0.insert(event.id, Outcome::Err);
+    }
+    for event in finished.iter() {
+        let outcome = pending.0.remove(&event.id).unwrap_or(Outcome::Ok);
+        debug!("Command {} sent by {:?} completed", event.id, event.target);
+        completed.send(CommandCompleted {
+            target: event.target,
+            id: event.id,
+            outcome,
         });
     }
 }
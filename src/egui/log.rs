@@ -0,0 +1,197 @@
+use std::fmt::{self, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+use bevy::log::Level;
+use bevy::prelude::*;
+use bevy::utils::tracing::{
+    field::{Field, Visit},
+    subscriber, Event, Subscriber,
+};
+use expedition::{Color32, MessageStyle, Styleable};
+use tracing_log::LogTracer;
+use tracing_subscriber::{layer::Context, prelude::*, EnvFilter, Layer, Registry};
+
+use super::{ConsoleUiConfig, LineKind, PushConsoleUiLine};
+
+/// A log event captured by a [`ConsoleLogLayer`].
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+/// Tracing layer which forwards log events to the console UI.
+///
+/// Bevy's `LogPlugin` does not allow adding layers, so either replace it with
+/// [`ConsoleLogPlugin`], or add this layer to your own subscriber and insert the
+/// [`ConsoleLogReceiver`] created with it.
+pub struct ConsoleLogLayer {
+    tx: Sender<LogRecord>,
+}
+
+impl ConsoleLogLayer {
+    pub fn new() -> (Self, ConsoleLogReceiver) {
+        let (tx, rx) = mpsc::channel();
+        (Self { tx }, ConsoleLogReceiver(Mutex::new(rx)))
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ConsoleLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        // the receiver only goes away when the app is dropped
+        let _ = self.tx.send(LogRecord {
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            message: visitor.0,
+        });
+    }
+}
+
+/// Formats the fields of an event in the same way as the default formatter: the message,
+/// followed by the other fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Receives the records captured by a [`ConsoleLogLayer`].
+#[derive(Resource)]
+pub struct ConsoleLogReceiver(Mutex<Receiver<LogRecord>>);
+
+/// Replacement for Bevy's `LogPlugin` which also shows log output in the console UI.
+///
+/// The `LogPlugin` must be disabled when using this plugin, as only one global subscriber can
+/// be set.
+pub struct ConsoleLogPlugin {
+    /// Filters logs using the [`EnvFilter`] format.
+    pub filter: String,
+    /// Filters out logs that are "less than" the given level.
+    pub level: Level,
+}
+
+impl Default for ConsoleLogPlugin {
+    fn default() -> Self {
+        Self {
+            filter: "wgpu=error,naga=warn".to_owned(),
+            level: Level::INFO,
+        }
+    }
+}
+
+impl Plugin for ConsoleLogPlugin {
+    fn build(&self, app: &mut App) {
+        let default_filter = format!("{},{}", self.level, self.filter);
+        let filter_layer = EnvFilter::try_from_default_env()
+            .or_else(|_| EnvFilter::try_new(&default_filter))
+            .unwrap_or_else(|e| {
+                eprintln!("Invalid log filter '{}': {}", default_filter, e);
+                EnvFilter::new(self.level.to_string())
+            });
+        let fmt_layer = tracing_subscriber::fmt::Layer::default().with_writer(std::io::stderr);
+        let (console_layer, receiver) = ConsoleLogLayer::new();
+        let subscriber = Registry::default()
+            .with(filter_layer)
+            .with(fmt_layer)
+            .with(console_layer);
+
+        let logger_already_set = LogTracer::init().is_err();
+        let subscriber_already_set = subscriber::set_global_default(subscriber).is_err();
+        if logger_already_set || subscriber_already_set {
+            warn!("Could not set global logger and tracing subscriber, as they are already set. Consider disabling LogPlugin.");
+        }
+
+        app.insert_resource(receiver);
+    }
+}
+
+/// Styles of log records shown in the console, by level.
+#[derive(Debug, Clone)]
+pub struct LogStyles {
+    pub error: MessageStyle,
+    pub warn: MessageStyle,
+    pub info: MessageStyle,
+    pub debug: MessageStyle,
+    pub trace: MessageStyle,
+}
+
+impl Default for LogStyles {
+    fn default() -> Self {
+        Self {
+            error: MessageStyle::new().color(Color32::RED),
+            warn: MessageStyle::new().color(Color32::YELLOW),
+            info: MessageStyle::new(),
+            debug: MessageStyle::new().color(Color32::GRAY),
+            trace: MessageStyle::new().color(Color32::DARK_GRAY),
+        }
+    }
+}
+
+impl LogStyles {
+    pub const fn get(&self, level: Level) -> MessageStyle {
+        match level {
+            Level::ERROR => self.error,
+            Level::WARN => self.warn,
+            Level::INFO => self.info,
+            Level::DEBUG => self.debug,
+            Level::TRACE => self.trace,
+        }
+    }
+}
+
+impl ConsoleUiConfig {
+    /// Checks if a log record should be shown in the console, using the most specific entry in
+    /// `log_targets` which matches its target, or `log_level` otherwise.
+    pub fn shows_log(&self, level: Level, target: &str) -> bool {
+        let max_level = self
+            .log_targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(Some(self.log_level), |(_, level)| *level);
+        // more verbose levels compare as greater
+        max_level.is_some_and(|max_level| level <= max_level)
+    }
+}
+
+pub(super) fn push_log_records(
+    receiver: Option<Res<ConsoleLogReceiver>>,
+    config: Res<ConsoleUiConfig>,
+    mut push_lines: EventWriter<PushConsoleUiLine>,
+) {
+    let Some(receiver) = receiver else {
+        return;
+    };
+    let Ok(rx) = receiver.0.lock() else {
+        return;
+    };
+    for record in rx
+        .try_iter()
+        .filter(|record| config.shows_log(record.level, &record.target))
+    {
+        let line = format!("{:>5} {}: {}", record.level, record.target, record.message);
+        push_lines.send(
+            PushConsoleUiLine::new(line.with_style(config.log_styles.get(record.level)))
+                .with_kind(LineKind::Log(record.level)),
+        );
+    }
+}
@@ -0,0 +1,1107 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::SystemTime;
+
+use bevy::{
+    app::PluginGroupBuilder,
+    log::Level,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_egui::{
+    egui::{
+        self,
+        text::{CCursor, LayoutJob, LayoutSection},
+        text_edit::CCursorRange,
+        FontId, TextStyle,
+    },
+    EguiContexts,
+};
+use expedition::{egui::StyleToFormat, Color32, Message, MessageStyle, Styleable};
+
+use crate::{
+    CommandBufInput, CommandResponse, CommandSet, CommandsPlugin, Completion,
+    InbuiltCommandsPlugin, Outcome, SharedCompletionData, DEFAULT_PROMPT,
+};
+
+pub mod log;
+pub mod syntax;
+
+use self::log::{push_log_records, LogStyles};
+use self::syntax::{layout_input, signature_hint, validation_hint, SyntaxStyles};
+
+pub struct EguiInputPlugin;
+
+impl Plugin for EguiInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ConsoleUiDispatch>()
+            .add_event::<PushConsoleUiLine>()
+            .add_event::<PushConsoleUiHistory>()
+            .insert_resource(ConsoleUiOpen(false))
+            .insert_resource(ConsoleUiConfig::default())
+            .init_resource::<SharedCompletionData>()
+            .add_systems(Startup, setup_default_console)
+            .add_systems(
+                Update,
+                (toggle_console_ui, sync_default_console_open, console_ui).chain(),
+            )
+            .add_systems(Update, (dispatch).in_set(CommandSet::Dispatch))
+            .add_systems(Update, (respond_default).in_set(CommandSet::Response))
+            .add_systems(
+                Update,
+                (push_log_records, push_lines, push_history)
+                    .chain()
+                    .after(CommandSet::Response),
+            );
+    }
+}
+
+/// The console spawned by [`EguiInputPlugin`], which is also its command sender.
+#[derive(Resource)]
+pub struct EguiCommandSender(pub Entity);
+
+#[derive(Component)]
+pub(crate) struct DefaultConsoleUi;
+
+fn setup_default_console(mut commands: Commands) {
+    let sender = commands
+        .spawn((ConsoleUiBundle::new("Console"), DefaultConsoleUi))
+        .id();
+    commands.insert_resource(EguiCommandSender(sender));
+}
+
+/// A console window. The entity is also the command sender of the commands entered into it,
+/// and the responses sent to it are shown in its scrollback.
+#[derive(Component)]
+pub struct ConsoleUi {
+    pub title: String,
+    pub prompt: String,
+    pub open: bool,
+}
+
+#[derive(Bundle)]
+pub struct ConsoleUiBundle {
+    pub name: Name,
+    pub console: ConsoleUi,
+    pub state: ConsoleUiState,
+}
+
+impl ConsoleUiBundle {
+    pub fn new(title: impl Into<String>) -> Self {
+        let title = title.into();
+        Self {
+            name: Name::new(title.clone()),
+            console: ConsoleUi {
+                title,
+                prompt: DEFAULT_PROMPT.into(),
+                open: false,
+            },
+            state: ConsoleUiState::default(),
+        }
+    }
+}
+
+/// Whether the default console is open.
+#[derive(Resource)]
+pub struct ConsoleUiOpen(pub bool);
+
+fn sync_default_console_open(
+    open: Res<ConsoleUiOpen>,
+    mut console: Query<&mut ConsoleUi, With<DefaultConsoleUi>>,
+) {
+    if !open.is_changed() {
+        return;
+    }
+    for mut console in &mut console {
+        console.open = open.0;
+    }
+}
+
+#[derive(Resource)]
+pub struct ConsoleUiConfig {
+    pub error_style: MessageStyle,
+    pub scrollback_cap: usize,
+    pub history_cap: usize,
+    /// Keys which open and close the default console. If this is empty, [`ConsoleUiOpen`] must
+    /// be set by the app instead.
+    pub toggle_keys: Vec<KeyCode>,
+    pub layout: ConsoleUiLayout,
+    /// Styles of the words in the input line.
+    pub syntax_styles: SyntaxStyles,
+    /// Styles of log records shown in the console, when using a
+    /// [`ConsoleLogLayer`](log::ConsoleLogLayer).
+    pub log_styles: LogStyles,
+    /// Most verbose level of log records shown in the console.
+    pub log_level: Level,
+    /// Overrides of `log_level` for targets under the given module paths. The most specific
+    /// path is used, and `None` hides all records under that path.
+    pub log_targets: HashMap<String, Option<Level>>,
+}
+
+/// How the console is placed on the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleUiLayout {
+    /// A floating window, which can be moved and resized.
+    Window,
+    /// A panel anchored to the top of the screen, which slides down when opened.
+    ///
+    /// `height` is the fraction of the screen height which the panel covers.
+    DropDown { height: f32 },
+}
+
+impl Default for ConsoleUiConfig {
+    fn default() -> Self {
+        Self {
+            error_style: MessageStyle::new().color(Color32::RED),
+            scrollback_cap: 10000,
+            history_cap: 100,
+            toggle_keys: vec![KeyCode::Grave, KeyCode::F1],
+            layout: ConsoleUiLayout::Window,
+            syntax_styles: SyntaxStyles::default(),
+            log_styles: LogStyles::default(),
+            log_level: Level::INFO,
+            log_targets: HashMap::from_iter([
+                ("wgpu".to_owned(), Some(Level::ERROR)),
+                ("naga".to_owned(), Some(Level::WARN)),
+                // issued commands are already shown in the scrollback
+                ("bevy_commands::egui".to_owned(), Some(Level::WARN)),
+            ]),
+        }
+    }
+}
+
+/// What produced a line in the scrollback, used to filter the scrollback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineKind {
+    /// A command line entered into the console.
+    Echo,
+    /// A response to a command.
+    Response(Outcome),
+    /// A log record.
+    Log(Level),
+    /// Any other line.
+    Other,
+}
+
+/// Font used for lines in the scrollback.
+const LINE_FONT: FontId = FontId::monospace(14.0);
+
+/// Kinds of lines which can be shown or hidden in the console, and their labels.
+const LINE_KIND_TOGGLES: [(LineKind, &str); 8] = [
+    (LineKind::Echo, "Echo"),
+    (LineKind::Response(Outcome::Ok), "Ok"),
+    (LineKind::Response(Outcome::Err), "Err"),
+    (LineKind::Log(Level::ERROR), "Error"),
+    (LineKind::Log(Level::WARN), "Warn"),
+    (LineKind::Log(Level::INFO), "Info"),
+    (LineKind::Log(Level::DEBUG), "Debug"),
+    (LineKind::Log(Level::TRACE), "Trace"),
+];
+
+/// A line in the console scrollback.
+#[derive(Debug, Clone)]
+pub struct ScrollbackEntry {
+    pub message: Message,
+    pub kind: LineKind,
+    /// The command sender which the line was sent by or to, if any.
+    pub sender: Option<Entity>,
+    pub timestamp: SystemTime,
+    /// Layout of each line of the message, built when the entry is pushed.
+    lines: Vec<LayoutJob>,
+}
+
+/// Lines of the scrollback which pass the current filters, as (entry, line) indices.
+#[derive(Default)]
+struct VisibleRows {
+    scrollback_generation: u64,
+    filter: String,
+    hidden_kinds: HashSet<LineKind>,
+    rows: Vec<(usize, usize)>,
+}
+
+#[derive(Component)]
+pub struct ConsoleUiState {
+    scrollback: Vec<ScrollbackEntry>,
+    pub buf: String,
+    /// Only lines containing this text, ignoring ASCII case, are shown in the scrollback.
+    pub filter: String,
+    /// Kinds of lines which are not shown in the scrollback.
+    pub hidden_kinds: HashSet<LineKind>,
+    /// Incremented whenever the scrollback changes.
+    scrollback_generation: u64,
+    visible_rows: VisibleRows,
+    history: VecDeque<String>,
+    history_index: usize,
+    completions: Vec<Completion>,
+    completion_start: usize,
+    completion_index: Option<usize>,
+    search: Option<HistorySearch>,
+    /// The line which `validation_error` was found for.
+    validated_buf: String,
+    validation_error: Option<String>,
+    /// Whether the console was open on the last frame.
+    was_open: bool,
+}
+
+/// An incremental reverse search through the history, started with Ctrl+R.
+struct HistorySearch {
+    query: String,
+    /// Index of the history entry which matches the query.
+    found: Option<usize>,
+    /// The line being edited before the search started, restored if it is cancelled.
+    saved_buf: String,
+}
+
+impl Default for ConsoleUiState {
+    fn default() -> Self {
+        Self {
+            scrollback: Vec::new(),
+            buf: String::new(),
+            filter: String::new(),
+            hidden_kinds: HashSet::new(),
+            scrollback_generation: 0,
+            visible_rows: VisibleRows::default(),
+            history: VecDeque::from([String::new()]),
+            history_index: 0,
+            completions: Vec::new(),
+            completion_start: 0,
+            completion_index: None,
+            search: None,
+            validated_buf: String::new(),
+            validation_error: None,
+            was_open: false,
+        }
+    }
+}
+
+impl ConsoleUiState {
+    pub const fn scrollback(&self) -> &Vec<ScrollbackEntry> {
+        &self.scrollback
+    }
+
+    /// Recomputes which lines of the scrollback are shown, if the scrollback or the filters
+    /// have changed since the last time.
+    fn update_visible_rows(&mut self) {
+        let visible = &mut self.visible_rows;
+        if visible.scrollback_generation == self.scrollback_generation
+            && visible.filter == self.filter
+            && visible.hidden_kinds == self.hidden_kinds
+        {
+            return;
+        }
+        visible.scrollback_generation = self.scrollback_generation;
+        visible.filter.clone_from(&self.filter);
+        visible.hidden_kinds.clone_from(&self.hidden_kinds);
+
+        let filter = self.filter.to_ascii_lowercase();
+        visible.rows = self
+            .scrollback
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !self.hidden_kinds.contains(&entry.kind))
+            .flat_map(|(i, entry)| {
+                entry
+                    .lines
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, line)| {
+                        filter.is_empty() || line.text.to_ascii_lowercase().contains(&filter)
+                    })
+                    .map(move |(j, _)| (i, j))
+            })
+            .collect();
+    }
+
+    /// Gets the previously entered lines, newest first.
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().skip(1).map(String::as_str)
+    }
+
+    /// Replaces the previously entered lines, given newest first.
+    pub fn set_history(&mut self, history: impl IntoIterator<Item = String>) {
+        self.history.truncate(1);
+        self.history.extend(history);
+        self.history_index = 0;
+    }
+
+    /// Gets the current reverse search query, if a search is in progress.
+    pub fn search_query(&self) -> Option<&str> {
+        self.search.as_ref().map(|search| search.query.as_str())
+    }
+
+    fn start_search(&mut self) {
+        self.completions.clear();
+        self.completion_index = None;
+        self.search = Some(HistorySearch {
+            query: String::new(),
+            found: None,
+            saved_buf: self.buf.clone(),
+        });
+    }
+
+    /// Finds the newest history entry matching the search query, starting at index `from`.
+    ///
+    /// If there is no match, the previous match is kept when `keep_found` is set.
+    fn search_history(&mut self, from: usize, keep_found: bool) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let found = self
+            .history
+            .iter()
+            .enumerate()
+            .skip(from.max(1))
+            .find(|(_, entry)| entry.contains(&search.query))
+            .map(|(i, _)| i);
+        if found.is_some() || !keep_found {
+            search.found = found;
+        }
+    }
+
+    /// Ends the search, replacing the line with the matched entry if `accept` is set, or
+    /// restoring the line from before the search otherwise.
+    fn end_search(&mut self, accept: bool) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+        self.buf = match search.found {
+            Some(found) if accept => self.history[found].clone(),
+            _ => search.saved_buf,
+        };
+        self.history_index = 0;
+    }
+
+    pub fn completions(&self) -> &[Completion] {
+        &self.completions
+    }
+
+    fn update_completions(&mut self, completions: &SharedCompletionData, cursor: usize) {
+        self.completion_index = None;
+        self.completions.clear();
+        if self.buf.trim().is_empty() {
+            return;
+        }
+        if let Ok(completions) = completions.0.read() {
+            (self.completion_start, self.completions) = completions.complete(&self.buf, cursor);
+        }
+    }
+
+    /// Validates the line being edited if it changed since it was last validated, returning
+    /// the error in it, if any.
+    fn validate(&mut self, completions: &SharedCompletionData) -> Option<&str> {
+        if self.validated_buf != self.buf {
+            self.validated_buf.clone_from(&self.buf);
+            self.validation_error = completions
+                .0
+                .read()
+                .ok()
+                .and_then(|completions| completions.validate(&self.buf).err());
+        }
+        self.validation_error.as_deref()
+    }
+
+    /// Replaces the word being completed with the candidate at `index`, returning the new
+    /// cursor position as a char index.
+    fn accept_completion(&mut self, index: usize, cursor: usize) -> usize {
+        let Some(completion) = self.completions.get(index) else {
+            return char_index(&self.buf, cursor);
+        };
+        let end = self.buf[cursor..]
+            .find(char::is_whitespace)
+            .map_or(self.buf.len(), |i| cursor + i)
+            .max(self.completion_start);
+        self.buf
+            .replace_range(self.completion_start..end, &completion.value);
+        let mut cursor = self.completion_start + completion.value.len();
+        if self.completions.len() == 1 {
+            if !self.buf[cursor..].starts_with(' ') {
+                self.buf.insert(cursor, ' ');
+            }
+            cursor += 1;
+            self.completions.clear();
+            self.completion_index = None;
+        }
+        char_index(&self.buf, cursor)
+    }
+}
+
+/// Sent when a line is entered into a console.
+#[derive(Event)]
+pub struct ConsoleUiDispatch {
+    pub console: Entity,
+    pub buf: String,
+}
+
+/// Adds a line to the scrollback of a console.
+#[derive(Event)]
+pub struct PushConsoleUiLine {
+    pub message: Message,
+    pub kind: LineKind,
+    pub sender: Option<Entity>,
+    /// The console to add the line to, or all consoles if this is `None`.
+    pub console: Option<Entity>,
+}
+
+impl PushConsoleUiLine {
+    pub fn new(message: impl Into<Message>) -> Self {
+        Self {
+            message: message.into(),
+            kind: LineKind::Other,
+            sender: None,
+            console: None,
+        }
+    }
+
+    pub const fn to_console(mut self, console: Entity) -> Self {
+        self.console = Some(console);
+        self
+    }
+
+    pub const fn with_kind(mut self, kind: LineKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub const fn with_sender(mut self, sender: Entity) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+}
+
+/// Adds a line to the history of a console.
+#[derive(Event)]
+pub struct PushConsoleUiHistory {
+    pub console: Entity,
+    pub line: String,
+}
+
+fn toggle_console_ui(
+    keys: Res<Input<KeyCode>>,
+    config: Res<ConsoleUiConfig>,
+    mut open: ResMut<ConsoleUiOpen>,
+) {
+    if keys.any_just_pressed(config.toggle_keys.iter().copied()) {
+        open.0 = !open.0;
+    }
+}
+
+fn console_ui(
+    mut egui: EguiContexts,
+    config: Res<ConsoleUiConfig>,
+    mut consoles: Query<(Entity, &ConsoleUi, &mut ConsoleUiState)>,
+    mut dispatch: EventWriter<ConsoleUiDispatch>,
+    completions: Res<SharedCompletionData>,
+) {
+    let ctx = egui.ctx_mut();
+    if consoles
+        .iter()
+        .any(|(_, console, state)| console.open != state.was_open)
+    {
+        // the key which toggled a console should not be typed into it
+        ctx.input_mut(|i| i.events.retain(|e| !matches!(e, egui::Event::Text(_))));
+    }
+
+    for (entity, console, mut state) in &mut consoles {
+        let opened = console.open && !state.was_open;
+        state.was_open = console.open;
+        let id = egui::Id::new(("console_ui", entity));
+        let mut contents = |ui: &mut egui::Ui| {
+            console_contents(
+                ui,
+                entity,
+                &mut state,
+                opened,
+                &config,
+                &mut dispatch,
+                &completions,
+            );
+        };
+
+        match config.layout {
+            ConsoleUiLayout::Window => {
+                if !console.open {
+                    continue;
+                }
+                egui::Window::new(&console.title)
+                    .id(id)
+                    .collapsible(false)
+                    .resizable(true)
+                    .default_size([800.0, 400.0])
+                    .show(ctx, contents);
+            }
+            ConsoleUiLayout::DropDown { height } => {
+                let openness = ctx.animate_bool(id, console.open);
+                if openness <= 0.0 {
+                    continue;
+                }
+                let screen = ctx.screen_rect();
+                let height = screen.height() * height;
+                egui::Area::new(id)
+                    .order(egui::Order::Foreground)
+                    .fixed_pos(screen.left_top() - egui::vec2(0.0, height * (1.0 - openness)))
+                    .show(ctx, |ui| {
+                        egui::Frame::window(ui.style())
+                            .rounding(0.0)
+                            .show(ui, |ui| {
+                                // don't take input while sliding closed
+                                ui.set_enabled(console.open);
+                                ui.set_width(screen.width() - ui.spacing().window_margin.sum().x);
+                                ui.set_height(height - ui.spacing().window_margin.sum().y);
+                                contents(ui);
+                            });
+                    });
+            }
+        }
+    }
+}
+
+/// Draws the scrollback and input line, and handles input to them.
+///
+/// `opened` is set on the frame on which the console was opened.
+fn console_contents(
+    ui: &mut egui::Ui,
+    console: Entity,
+    state: &mut ConsoleUiState,
+    opened: bool,
+    config: &ConsoleUiConfig,
+    dispatch: &mut EventWriter<ConsoleUiDispatch>,
+    completions: &SharedCompletionData,
+) {
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut state.filter)
+                    .hint_text("Filter")
+                    .desired_width(200.0),
+            );
+            for (kind, label) in LINE_KIND_TOGGLES {
+                let mut shown = !state.hidden_kinds.contains(&kind);
+                if ui.toggle_value(&mut shown, label).changed() {
+                    if shown {
+                        state.hidden_kinds.remove(&kind);
+                    } else {
+                        state.hidden_kinds.insert(kind);
+                    }
+                }
+            }
+        });
+
+        state.update_visible_rows();
+        let filter = state.filter.to_ascii_lowercase();
+        let highlight = ui.visuals().selection.bg_fill;
+        let row_height = ui.fonts(|fonts| fonts.row_height(&LINE_FONT));
+        egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .max_height(ui.available_height() - 30.0)
+            .stick_to_bottom(true)
+            .show_rows(
+                ui,
+                row_height,
+                state.visible_rows.rows.len(),
+                |ui, range| {
+                    for &(entry, line) in &state.visible_rows.rows[range] {
+                        let mut job = state.scrollback[entry].lines[line].clone();
+                        if !filter.is_empty() {
+                            let matches = find_matches(&job.text, &filter);
+                            highlight_job(&mut job, &matches, highlight);
+                        }
+                        ui.add(egui::Label::new(job).wrap(false));
+                    }
+                },
+            );
+
+        ui.separator();
+
+        // Enter submits the input, and Shift+Enter inserts a newline
+        let buf_edit_id = ui.id().with("input");
+        let entered = ui.memory(|m| m.has_focus(buf_edit_id))
+            && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter));
+        // Tab cycles through completions or accepts a search, so keep the editor from inserting it
+        let (tab, shift_tab) = if ui.memory(|m| m.has_focus(buf_edit_id))
+            && (state.search.is_some() || !state.completions.is_empty())
+        {
+            ui.input_mut(|i| {
+                (
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::Tab),
+                    i.consume_key(egui::Modifiers::SHIFT, egui::Key::Tab),
+                )
+            })
+        } else {
+            (false, false)
+        };
+
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            layout_input(ui, completions, &config.syntax_styles, text, wrap_width)
+        };
+        // while searching, typing edits the search query instead of the line
+        let buf_edit = match &mut state.search {
+            Some(search) => {
+                egui::TextEdit::singleline(&mut search.query).hint_text("reverse-i-search")
+            }
+            None => egui::TextEdit::multiline(&mut state.buf)
+                .desired_rows(1)
+                .layouter(&mut layouter),
+        }
+        .id(buf_edit_id)
+        .desired_width(f32::INFINITY)
+        .lock_focus(true)
+        .font(TextStyle::Monospace);
+
+        let buf_edit_output = buf_edit.show(ui);
+        if state.search.is_none() {
+            // only hint at the rest of the command when typing at the end of it
+            let at_end = buf_edit_output
+                .cursor_range
+                .is_some_and(|range| range.primary.ccursor.index == state.buf.chars().count());
+            let hint = completions
+                .0
+                .read()
+                .ok()
+                .filter(|_| at_end && buf_edit_output.response.has_focus())
+                .and_then(|completions| completions.signature_hint(&state.buf));
+            if let Some(hint) = hint {
+                signature_hint(ui, &buf_edit_output, &hint);
+            }
+            if let Some(error) = state.validate(completions) {
+                validation_hint(ui, error, config.error_style);
+            }
+        }
+        let buf_edit_resp = buf_edit_output.response;
+        let search_pressed = buf_edit_resp.has_focus()
+            && ui.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::R));
+
+        if entered || opened {
+            ui.memory_mut(|m| m.request_focus(buf_edit_resp.id));
+        }
+
+        if entered {
+            state.end_search(true);
+        }
+
+        if state.search.is_some() {
+            if buf_edit_resp.changed() {
+                state.search_history(1, false);
+            }
+            if search_pressed {
+                let from = state
+                    .search
+                    .as_ref()
+                    .and_then(|s| s.found)
+                    .map_or(1, |i| i + 1);
+                state.search_history(from, true);
+            }
+
+            let (escape, accept) = ui.input(|i| {
+                (
+                    i.key_pressed(egui::Key::Escape),
+                    tab || shift_tab
+                        || i.key_pressed(egui::Key::ArrowUp)
+                        || i.key_pressed(egui::Key::ArrowDown),
+                )
+            });
+            if escape || accept {
+                state.end_search(accept);
+                ui.memory_mut(|m| m.request_focus(buf_edit_resp.id));
+                set_cursor_pos(ui.ctx(), buf_edit_resp.id, state.buf.chars().count());
+            } else if let Some(search) = &state.search {
+                search_popup(ui, &buf_edit_resp, search, &state.history);
+            }
+            return;
+        }
+
+        if search_pressed {
+            state.start_search();
+            return;
+        }
+
+        if entered {
+            let buf = std::mem::take(&mut state.buf);
+            state.history_index = 0;
+            state.completions.clear();
+            // each line is sent as its own command, so a pasted block runs line by line
+            for line in buf.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                dispatch.send(ConsoleUiDispatch {
+                    console,
+                    buf: line.to_owned(),
+                });
+            }
+        }
+
+        let cursor = cursor_pos(ui.ctx(), buf_edit_resp.id, &state.buf);
+        if buf_edit_resp.changed() {
+            state.update_completions(completions, cursor);
+        } else if cursor < state.completion_start {
+            // the cursor moved out of the word being completed
+            state.completions.clear();
+            state.completion_index = None;
+        }
+
+        if buf_edit_resp.has_focus() && !state.completions.is_empty() {
+            let escape = ui.input(|i| i.key_pressed(egui::Key::Escape));
+            if escape {
+                state.completions.clear();
+            } else if tab || shift_tab {
+                let len = state.completions.len();
+                let index = match (state.completion_index, shift_tab) {
+                    (None, false) => 0,
+                    (None, true) => len - 1,
+                    (Some(i), false) => (i + 1) % len,
+                    (Some(i), true) => (i + len - 1) % len,
+                };
+                state.completion_index = Some(index);
+                let pos = state.accept_completion(index, cursor);
+                set_cursor_pos(ui.ctx(), buf_edit_resp.id, pos);
+            }
+        }
+
+        if let Some(index) = completion_popup(
+            ui,
+            &buf_edit_resp,
+            &state.completions,
+            state.completion_index,
+        ) {
+            state.completion_index = Some(index);
+            let pos = state.accept_completion(index, cursor);
+            set_cursor_pos(ui.ctx(), buf_edit_resp.id, pos);
+            ui.memory_mut(|m| m.request_focus(buf_edit_resp.id));
+        }
+
+        // the arrow keys move between lines when editing more than one
+        let single_line = !state.buf.contains('\n');
+        if buf_edit_resp.has_focus()
+            && single_line
+            && ui.input(|i| i.key_pressed(egui::Key::ArrowUp))
+            && state.history.len() > 1
+            && state.history_index < state.history.len() - 1
+        {
+            if state.history_index == 0 && !state.buf.trim().is_empty() {
+                state.history[0] = state.buf.to_owned();
+            }
+
+            state.history_index += 1;
+            state.buf = state.history[state.history_index].clone();
+            state.completions.clear();
+
+            set_cursor_pos(ui.ctx(), buf_edit_resp.id, state.buf.len());
+        } else if buf_edit_resp.has_focus()
+            && single_line
+            && ui.input(|i| i.key_pressed(egui::Key::ArrowDown))
+            && state.history_index > 0
+        {
+            state.history_index -= 1;
+            state.buf = state.history[state.history_index].clone();
+            state.completions.clear();
+
+            set_cursor_pos(ui.ctx(), buf_edit_resp.id, state.buf.len());
+        }
+    });
+}
+
+/// Shows the entry matched by a history search under the input line, with the matching part
+/// highlighted.
+fn search_popup(
+    ui: &egui::Ui,
+    buf_edit_resp: &egui::Response,
+    search: &HistorySearch,
+    history: &VecDeque<String>,
+) {
+    let font_id = FontId::monospace(14.0);
+    let text_color = ui.visuals().text_color();
+    let weak_color = ui.visuals().weak_text_color();
+    let mut job = egui::text::LayoutJob::default();
+    let mut append = |text: &str, format: egui::TextFormat| job.append(text, 0.0, format);
+    let plain = egui::TextFormat::simple(font_id.clone(), text_color);
+    let weak = egui::TextFormat::simple(font_id, weak_color);
+
+    match search.found.map(|i| history[i].as_str()) {
+        Some(entry) => {
+            append("(reverse-i-search) ", weak);
+            let start = entry.find(&search.query).unwrap_or(0);
+            let end = start + search.query.len();
+            append(&entry[..start], plain.clone());
+            append(
+                &entry[start..end],
+                egui::TextFormat {
+                    background: ui.visuals().selection.bg_fill,
+                    ..plain.clone()
+                },
+            );
+            append(&entry[end..], plain);
+        }
+        None => append("(failed reverse-i-search)", weak),
+    }
+
+    egui::Area::new(buf_edit_resp.id.with("search"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(buf_edit_resp.rect.left_bottom())
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(job);
+            });
+        });
+}
+
+/// Shows the completion candidates under the input line, returning the index of the candidate
+/// which was clicked, if any.
+fn completion_popup(
+    ui: &egui::Ui,
+    buf_edit_resp: &egui::Response,
+    completions: &[Completion],
+    selected: Option<usize>,
+) -> Option<usize> {
+    if completions.is_empty() {
+        return None;
+    }
+
+    let mut clicked = None;
+    egui::Area::new(buf_edit_resp.id.with("completions"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(buf_edit_resp.rect.left_bottom())
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for (i, completion) in completions.iter().enumerate() {
+                            let resp = ui.selectable_label(
+                                selected == Some(i),
+                                egui::RichText::new(completion.display()).monospace(),
+                            );
+                            if selected == Some(i) {
+                                resp.scroll_to_me(None);
+                            }
+                            if resp.clicked() {
+                                clicked = Some(i);
+                            }
+                        }
+                    });
+            });
+        });
+    clicked
+}
+
+/// Splits a layout job into one job per line of its text.
+fn split_lines(job: &LayoutJob) -> Vec<LayoutJob> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for text in job.text.split('\n') {
+        let end = start + text.len();
+        let mut sections = job
+            .sections
+            .iter()
+            .filter(|section| section.byte_range.start < end && section.byte_range.end > start)
+            .map(|section| LayoutSection {
+                leading_space: 0.0,
+                byte_range: (section.byte_range.start.max(start) - start)
+                    ..(section.byte_range.end.min(end) - start),
+                format: section.format.clone(),
+            })
+            .collect::<Vec<_>>();
+        // empty lines still need a format to be given a height
+        if sections.is_empty() {
+            if let Some(section) = job.sections.first() {
+                sections.push(LayoutSection {
+                    leading_space: 0.0,
+                    byte_range: 0..0,
+                    format: section.format.clone(),
+                });
+            }
+        }
+        lines.push(LayoutJob {
+            text: text.to_owned(),
+            sections,
+            ..Default::default()
+        });
+        start = end + 1;
+    }
+    lines
+}
+
+/// Finds the byte ranges of the non-overlapping occurrences of `pattern` in `text`, ignoring
+/// ASCII case. `pattern` must already be lowercase.
+fn find_matches(text: &str, pattern: &str) -> Vec<Range<usize>> {
+    let text = text.to_ascii_lowercase();
+    let mut matches = Vec::new();
+    let mut from = 0;
+    while let Some(i) = text[from..].find(pattern) {
+        let start = from + i;
+        from = start + pattern.len();
+        matches.push(start..from);
+    }
+    matches
+}
+
+/// Sets the background of the parts of `job` within `ranges`, splitting its sections where
+/// needed. `ranges` must be sorted and not overlap.
+fn highlight_job(job: &mut LayoutJob, ranges: &[Range<usize>], background: Color32) {
+    let mut sections = Vec::with_capacity(job.sections.len());
+    for section in job.sections.drain(..) {
+        let Range { mut start, end } = section.byte_range;
+        let mut bounds = ranges
+            .iter()
+            .flat_map(|range| [range.start, range.end])
+            .filter(|bound| *bound > start && *bound < end)
+            .collect::<Vec<_>>();
+        bounds.push(end);
+
+        for bound in bounds {
+            let mut format = section.format.clone();
+            if ranges.iter().any(|range| range.contains(&start)) {
+                format.background = background;
+            }
+            sections.push(LayoutSection {
+                leading_space: if start == section.byte_range.start {
+                    section.leading_space
+                } else {
+                    0.0
+                },
+                byte_range: start..bound,
+                format,
+            });
+            start = bound;
+        }
+    }
+    job.sections = sections;
+}
+
+/// Gets the cursor position in the text edit with the given ID as a byte index into `buf`.
+fn cursor_pos(ctx: &egui::Context, id: egui::Id, buf: &str) -> usize {
+    egui::TextEdit::load_state(ctx, id)
+        .and_then(|state| state.ccursor_range())
+        .and_then(|range| buf.char_indices().nth(range.primary.index))
+        .map_or(buf.len(), |(i, _)| i)
+}
+
+fn char_index(buf: &str, byte_index: usize) -> usize {
+    buf[..byte_index].chars().count()
+}
+
+fn set_cursor_pos(ctx: &egui::Context, id: egui::Id, pos: usize) {
+    if let Some(mut state) = egui::TextEdit::load_state(ctx, id) {
+        state.set_ccursor_range(Some(CCursorRange::one(CCursor::new(pos))));
+        state.store(ctx, id);
+    }
+}
+
+fn dispatch(
+    mut events: EventReader<ConsoleUiDispatch>,
+    mut push_line: EventWriter<PushConsoleUiLine>,
+    mut push_history: EventWriter<PushConsoleUiHistory>,
+    mut command_input: EventWriter<CommandBufInput>,
+    consoles: Query<&ConsoleUi>,
+) {
+    for event in events.iter() {
+        let buf = event.buf.clone();
+        let Ok(console) = consoles.get(event.console) else {
+            continue;
+        };
+        if !buf.is_empty() {
+            info!("Issued console command: {}", buf);
+            push_line.send(
+                PushConsoleUiLine::new(format!("{}{}", console.prompt, buf))
+                    .with_kind(LineKind::Echo)
+                    .with_sender(event.console)
+                    .to_console(event.console),
+            );
+            push_history.send(PushConsoleUiHistory {
+                console: event.console,
+                line: buf.clone(),
+            });
+            command_input.send(CommandBufInput::new(event.console, buf));
+        }
+    }
+}
+
+fn respond_default(
+    mut events: EventReader<CommandResponse>,
+    consoles: Query<(), With<ConsoleUi>>,
+    ui_config: Res<ConsoleUiConfig>,
+    mut push_lines: EventWriter<PushConsoleUiLine>,
+) {
+    for resp in events.iter().filter(|r| consoles.contains(r.target)) {
+        let message = match resp.outcome {
+            Outcome::Ok => resp.message.clone(),
+            Outcome::Err => resp.message.clone().with_style(ui_config.error_style),
+        };
+        push_lines.send(
+            PushConsoleUiLine::new(message)
+                .with_kind(LineKind::Response(resp.outcome))
+                .with_sender(resp.target)
+                .to_console(resp.target),
+        );
+    }
+}
+
+fn push_lines(
+    mut events: EventReader<PushConsoleUiLine>,
+    config: Res<ConsoleUiConfig>,
+    mut consoles: Query<(Entity, &mut ConsoleUiState)>,
+) {
+    if events.is_empty() {
+        return;
+    }
+    let formatter = StyleToFormat {
+        font_id: LINE_FONT,
+        ..Default::default()
+    };
+    for event in events.iter() {
+        let entry = ScrollbackEntry {
+            message: event.message.clone(),
+            kind: event.kind,
+            sender: event.sender,
+            timestamp: SystemTime::now(),
+            lines: split_lines(&formatter.to_job(&event.message)),
+        };
+        for (_, mut state) in consoles
+            .iter_mut()
+            .filter(|(entity, _)| event.console.is_none_or(|console| console == *entity))
+        {
+            state.scrollback.push(entry.clone());
+            state.scrollback_generation += 1;
+        }
+    }
+
+    let cap = config.scrollback_cap + 1;
+    for (_, mut state) in &mut consoles {
+        let len = state.scrollback.len();
+        if len > cap {
+            state.scrollback.drain(0..(len - cap));
+            // the cached rows index into the scrollback, so they are stale once entries shift
+            state.scrollback_generation += 1;
+        }
+    }
+}
+
+fn push_history(
+    mut events: EventReader<PushConsoleUiHistory>,
+    config: Res<ConsoleUiConfig>,
+    mut consoles: Query<&mut ConsoleUiState>,
+) {
+    for event in events.iter() {
+        let Ok(mut state) = consoles.get_mut(event.console) else {
+            continue;
+        };
+        if state
+            .history
+            .get(1)
+            .map(|s| *s != event.line)
+            .unwrap_or(true)
+        {
+            state.history.insert(1, event.line.clone());
+        }
+        // the first entry is the line currently being edited
+        let cap = config.history_cap + 1;
+        state.history.truncate(cap);
+    }
+}
+
+pub struct CommandsEguiPlugins;
+
+impl PluginGroup for CommandsEguiPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(CommandsPlugin)
+            .add(InbuiltCommandsPlugin)
+            .add(EguiInputPlugin)
+    }
+}
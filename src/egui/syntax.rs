@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use bevy_egui::egui::{self, text::LayoutJob, text_edit::TextEditOutput, Align2, Galley};
+use expedition::{egui::StyleToFormat, Color32, MessageStyle, Styleable};
+
+use super::LINE_FONT;
+use crate::{SharedCompletionData, SignatureHint, SyntaxKind};
+
+/// Styles of the words in the console input line, by their role in the command.
+#[derive(Debug, Clone)]
+pub struct SyntaxStyles {
+    pub command: MessageStyle,
+    pub cvar: MessageStyle,
+    pub subcommand: MessageStyle,
+    pub flag: MessageStyle,
+    pub value: MessageStyle,
+    pub positional: MessageStyle,
+    pub operator: MessageStyle,
+    /// Style of unknown commands and flags, and of unexpected positional arguments.
+    pub unknown: MessageStyle,
+}
+
+impl Default for SyntaxStyles {
+    fn default() -> Self {
+        Self {
+            command: MessageStyle::new().color(Color32::GREEN),
+            cvar: MessageStyle::new().color(Color32::LIGHT_BLUE),
+            subcommand: MessageStyle::new().color(Color32::LIGHT_GREEN),
+            flag: MessageStyle::new().color(Color32::GOLD),
+            value: MessageStyle::new().color(Color32::LIGHT_YELLOW),
+            positional: MessageStyle::new(),
+            operator: MessageStyle::new().color(Color32::GRAY),
+            unknown: MessageStyle::new().color(Color32::RED),
+        }
+    }
+}
+
+impl SyntaxStyles {
+    pub const fn get(&self, kind: SyntaxKind) -> MessageStyle {
+        match kind {
+            SyntaxKind::Command => self.command,
+            SyntaxKind::Cvar => self.cvar,
+            SyntaxKind::Subcommand => self.subcommand,
+            SyntaxKind::Flag => self.flag,
+            SyntaxKind::Value => self.value,
+            SyntaxKind::Positional => self.positional,
+            SyntaxKind::Operator => self.operator,
+            SyntaxKind::UnknownCommand
+            | SyntaxKind::UnknownFlag
+            | SyntaxKind::UnexpectedPositional => self.unknown,
+        }
+    }
+}
+
+/// Lays out the input line with its words styled by their role, for use as the layouter of a
+/// [`egui::TextEdit`].
+pub(super) fn layout_input(
+    ui: &egui::Ui,
+    completions: &SharedCompletionData,
+    styles: &SyntaxStyles,
+    text: &str,
+    wrap_width: f32,
+) -> Arc<Galley> {
+    let formatter = StyleToFormat {
+        font_id: LINE_FONT,
+        default_color: ui.visuals().text_color(),
+        ..Default::default()
+    };
+    let spans = completions
+        .0
+        .read()
+        .map(|completions| completions.highlight(text))
+        .unwrap_or_default();
+
+    let mut job = LayoutJob::default();
+    let mut end = 0;
+    for span in spans {
+        if span.range.start > end {
+            job.append(
+                &text[end..span.range.start],
+                0.0,
+                formatter.to_format(MessageStyle::new()),
+            );
+        }
+        job.append(
+            &text[span.range.clone()],
+            0.0,
+            formatter.to_format(styles.get(span.kind)),
+        );
+        end = span.range.end;
+    }
+    // always add a section, so that an empty line is given a height
+    job.append(&text[end..], 0.0, formatter.to_format(MessageStyle::new()));
+    job.wrap.max_width = wrap_width;
+    ui.fonts(|fonts| fonts.layout_job(job))
+}
+
+/// Shows the error in the input line under it, before it is entered.
+pub(super) fn validation_hint(ui: &mut egui::Ui, error: &str, style: MessageStyle) {
+    let formatter = StyleToFormat {
+        font_id: LINE_FONT,
+        default_color: ui.visuals().weak_text_color(),
+        ..Default::default()
+    };
+    ui.add(egui::Label::new(formatter.to_job(&error.with_style(style))).wrap(true));
+}
+
+/// Shows the usage of the rest of the command greyed out after the end of the input line, and
+/// the help text of the argument being typed under it.
+pub(super) fn signature_hint(ui: &mut egui::Ui, output: &TextEditOutput, hint: &SignatureHint) {
+    let galley = &output.galley;
+    let end = output.text_draw_pos + galley.pos_from_cursor(&galley.end()).min.to_vec2();
+    ui.painter().with_clip_rect(output.text_clip_rect).text(
+        end,
+        Align2::LEFT_TOP,
+        &hint.usage,
+        LINE_FONT,
+        ui.visuals().weak_text_color(),
+    );
+    if let Some(help) = &hint.help {
+        ui.add(egui::Label::new(egui::RichText::new(help).font(LINE_FONT).weak()).wrap(true));
+    }
+}
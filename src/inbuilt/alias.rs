@@ -0,0 +1,70 @@
+use bevy::prelude::{Res, ResMut};
+
+use crate::{
+    self as bevy_commands, respond_err, respond_ok, CommandAliases, CommandMetaMap, UserAliases,
+};
+use crate::{AppCommand, CommandResponder, QueuedCommands};
+
+/// Defines an alias which expands to a command line, or lists the defined aliases.
+#[derive(clap::Parser, AppCommand)]
+#[command(name = "alias")]
+pub struct Alias {
+    /// The name of the alias.
+    pub name: Option<String>,
+    /// The command line which the alias expands to.
+    pub expansion: Option<String>,
+}
+
+pub fn alias(
+    mut queue: QueuedCommands<Alias>,
+    mut user_aliases: ResMut<UserAliases>,
+    command_meta: Res<CommandMetaMap>,
+    aliases: Res<CommandAliases>,
+) {
+    queue.consume(|mut ctx| match (&ctx.data.name, &ctx.data.expansion) {
+        (None, _) => {
+            if user_aliases.0.is_empty() {
+                ctx.ok("No aliases defined");
+                return;
+            }
+            let mut entries = user_aliases.0.iter().collect::<Vec<_>>();
+            entries.sort_unstable();
+            ctx.ok("Defined aliases:");
+            for (name, expansion) in entries {
+                respond_ok!(ctx, "  {} = {}", name, expansion);
+            }
+        }
+        (Some(name), None) => match user_aliases.0.get(name) {
+            Some(expansion) => respond_ok!(ctx, "{} = {}", name, expansion),
+            None => respond_err!(ctx, "No such alias: {}", name),
+        },
+        (Some(name), Some(expansion)) => {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                respond_err!(ctx, "Invalid alias name: {}", name);
+            } else if command_meta.0.contains_key(name.as_str()) || aliases.0.contains_key(name) {
+                respond_err!(ctx, "Cannot alias existing command {}", name);
+            } else {
+                user_aliases.0.insert(name.clone(), expansion.clone());
+                respond_ok!(ctx, "{} = {}", name, expansion);
+            }
+        }
+    });
+}
+
+/// Removes an alias defined with `alias`.
+#[derive(clap::Parser, AppCommand)]
+#[command(name = "unalias")]
+pub struct Unalias {
+    /// The name of the alias.
+    pub name: String,
+}
+
+pub fn unalias(mut queue: QueuedCommands<Unalias>, mut user_aliases: ResMut<UserAliases>) {
+    queue.consume(|mut ctx| {
+        let name = &ctx.data.name;
+        match user_aliases.0.remove(name) {
+            Some(_) => respond_ok!(ctx, "Removed alias {}", name),
+            None => respond_err!(ctx, "No such alias: {}", name),
+        }
+    });
+}
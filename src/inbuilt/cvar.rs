@@ -0,0 +1,135 @@
+use bevy::prelude::{EventWriter, Res};
+
+use crate::cvar::{CvarMetaMap, CvarOp, CvarRequest, CVAR_VALUE_NAME};
+use crate::plugin::suggest_names;
+use crate::{self as bevy_commands, respond_ok};
+use crate::{AppCommand, CommandResponder, QueuedCommands};
+
+/// Sets the value of a console variable.
+#[derive(clap::Parser, AppCommand)]
+#[command(name = "set")]
+pub struct Set {
+    /// The name of the variable.
+    #[arg(value_name = CVAR_VALUE_NAME)]
+    pub name: String,
+    /// The new value.
+    #[arg(allow_hyphen_values = true)]
+    pub value: String,
+}
+
+pub fn set(
+    mut queue: QueuedCommands<Set>,
+    cvar_meta: Res<CvarMetaMap>,
+    mut requests: EventWriter<CvarRequest>,
+) {
+    queue.consume(|mut ctx| {
+        let name = ctx.data.name.clone();
+        if !cvar_meta.0.contains_key(&name) {
+            ctx.err(no_such_cvar(&name, &cvar_meta));
+            return;
+        }
+        let op = CvarOp::Set(ctx.data.value.clone());
+        requests.send(CvarRequest {
+            handle: ctx.defer(),
+            name,
+            op,
+        });
+    });
+}
+
+/// Shows the value of a console variable.
+#[derive(clap::Parser, AppCommand)]
+#[command(name = "get")]
+pub struct Get {
+    /// The name of the variable.
+    #[arg(value_name = CVAR_VALUE_NAME)]
+    pub name: String,
+}
+
+pub fn get(mut queue: QueuedCommands<Get>, cvar_meta: Res<CvarMetaMap>) {
+    queue.consume(|mut ctx| {
+        let name = &ctx.data.name;
+        match cvar_meta.0.get(name) {
+            Some(meta) => respond_ok!(ctx, "{} = {}", name, meta.value),
+            None => ctx.err(no_such_cvar(name, &cvar_meta)),
+        }
+    });
+}
+
+/// Resets a console variable to its default value.
+#[derive(clap::Parser, AppCommand)]
+#[command(name = "reset")]
+pub struct Reset {
+    /// The name of the variable.
+    #[arg(value_name = CVAR_VALUE_NAME)]
+    pub name: String,
+}
+
+pub fn reset(
+    mut queue: QueuedCommands<Reset>,
+    cvar_meta: Res<CvarMetaMap>,
+    mut requests: EventWriter<CvarRequest>,
+) {
+    queue.consume(|mut ctx| {
+        let name = ctx.data.name.clone();
+        if !cvar_meta.0.contains_key(&name) {
+            ctx.err(no_such_cvar(&name, &cvar_meta));
+            return;
+        }
+        requests.send(CvarRequest {
+            handle: ctx.defer(),
+            name,
+            op: CvarOp::Reset,
+        });
+    });
+}
+
+/// Lists console variables and their values.
+#[derive(clap::Parser, AppCommand)]
+#[command(name = "cvarlist")]
+pub struct CvarList {
+    /// Only list variables whose names contain this text.
+    pub filter: Option<String>,
+}
+
+pub fn cvarlist(mut queue: QueuedCommands<CvarList>, cvar_meta: Res<CvarMetaMap>) {
+    queue.consume(|mut ctx| {
+        let filter = ctx.data.filter.as_deref().unwrap_or_default();
+        let mut cvars = cvar_meta
+            .0
+            .iter()
+            .filter(|(name, _)| name.contains(filter))
+            .collect::<Vec<_>>();
+        if cvars.is_empty() {
+            ctx.ok("No cvars found");
+            return;
+        }
+        cvars.sort_unstable_by_key(|(name, _)| name.as_str());
+
+        let longest_name_len = cvars.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, meta) in cvars {
+            let indent = " ".repeat(longest_name_len - name.len());
+            let message = format!(
+                "  {}{} = {} (default {})",
+                name, indent, meta.value, meta.default_value
+            );
+            match &meta.description {
+                Some(description) => respond_ok!(ctx, "{} - {}", message, description),
+                None => ctx.ok(message),
+            }
+        }
+    });
+}
+
+pub(super) fn no_such_cvar(name: &str, cvar_meta: &CvarMetaMap) -> String {
+    let suggestions = suggest_names(name, cvar_meta.0.keys().map(String::as_str));
+    if suggestions.is_empty() {
+        format!("No such cvar: {}", name)
+    } else {
+        format!(
+            "No such cvar: {}. Did you mean: {}?",
+            name,
+            suggestions.join(", ")
+        )
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use crate::{self as bevy_commands, respond_err, respond_ok};
+use crate::{
+    AppCommand, CommandBufInput, CommandCompleted, CommandId, CommandResponder, CommandResponse,
+    CommandSet, Outcome, QueuedCommands, ResponseHandle,
+};
+
+/// Runs each line of a file as a command, in order.
+///
+/// Blank lines and lines starting with `#` are ignored.
+#[derive(clap::Parser, AppCommand)]
+#[command(name = "exec")]
+pub struct Exec {
+    /// Path to the file to run.
+    pub path: PathBuf,
+}
+
+/// A script being run by `exec`, waiting for its current line to complete.
+struct ExecScript {
+    handle: ResponseHandle,
+    path: PathBuf,
+    lines: VecDeque<(usize, String)>,
+    current: Option<(CommandId, usize)>,
+    failed: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct ExecScripts(Vec<ExecScript>);
+
+pub fn exec(
+    mut queue: QueuedCommands<Exec>,
+    mut scripts: ResMut<ExecScripts>,
+    mut buf_input: EventWriter<CommandBufInput>,
+) {
+    queue.consume(|mut ctx| {
+        let path = ctx.data.path.clone();
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if scripts.0.iter().any(|script| {
+            fs::canonicalize(&script.path).unwrap_or_else(|_| script.path.clone()) == canonical
+        }) {
+            respond_err!(ctx, "{} is already being run", path.display());
+            return;
+        }
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                respond_err!(ctx, "Could not read {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let mut lines = VecDeque::new();
+        let mut invalid = false;
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if shlex::split(line).is_none() {
+                respond_err!(
+                    ctx,
+                    "{}:{}: could not parse: {}",
+                    path.display(),
+                    i + 1,
+                    line
+                );
+                invalid = true;
+            }
+            lines.push_back((i + 1, line.to_owned()));
+        }
+        if invalid {
+            return;
+        }
+
+        let mut script = ExecScript {
+            handle: ctx.defer(),
+            path,
+            lines,
+            current: None,
+            failed: 0,
+        };
+        run_next_line(&mut script, &mut buf_input);
+        scripts.0.push(script);
+    });
+}
+
+fn run_next_line(script: &mut ExecScript, buf_input: &mut EventWriter<CommandBufInput>) {
+    script.current = script.lines.pop_front().map(|(line_no, line)| {
+        let input = CommandBufInput::new(script.handle.sender, line);
+        let id = input.id;
+        buf_input.send(input);
+        (id, line_no)
+    });
+}
+
+pub fn advance_exec_scripts(
+    mut completed: EventReader<CommandCompleted>,
+    mut scripts: ResMut<ExecScripts>,
+    mut buf_input: EventWriter<CommandBufInput>,
+) {
+    for event in completed.iter() {
+        let Some(script) = scripts
+            .0
+            .iter_mut()
+            .find(|script| matches!(script.current, Some((id, _)) if id == event.id))
+        else {
+            continue;
+        };
+        if let (Outcome::Err, Some((_, line_no))) = (event.outcome, script.current) {
+            script.failed += 1;
+            let path = script.path.display().to_string();
+            let handle = &mut script.handle;
+            respond_err!(handle, "{}:{}: command failed", path, line_no);
+        }
+        run_next_line(script, &mut buf_input);
+    }
+
+    scripts.0.retain_mut(|script| {
+        if script.current.is_some() {
+            return true;
+        }
+        let path = script.path.display().to_string();
+        let mut handle = script.handle.clone();
+        match script.failed {
+            0 => respond_ok!(handle, "Finished running {}", path),
+            failed => respond_err!(handle, "Finished running {} with {} errors", path, failed),
+        }
+        handle.finish();
+        false
+    });
+}
+
+/// Runs a script file using `exec` when the app starts, if the file exists.
+pub struct AutoexecPlugin {
+    pub path: PathBuf,
+}
+
+impl Default for AutoexecPlugin {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("autoexec.cfg"),
+        }
+    }
+}
+
+impl Plugin for AutoexecPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AutoexecPath(self.path.clone()))
+            .add_systems(PostStartup, run_autoexec)
+            .add_systems(Update, respond_autoexec.in_set(CommandSet::Response));
+    }
+}
+
+#[derive(Resource)]
+struct AutoexecPath(PathBuf);
+
+#[derive(Component)]
+struct AutoexecCommandSender;
+
+fn run_autoexec(
+    mut commands: Commands,
+    path: Res<AutoexecPath>,
+    mut buf_input: EventWriter<CommandBufInput>,
+) {
+    if !path.0.exists() {
+        debug!("No autoexec file found at {}", path.0.display());
+        return;
+    }
+    let path = path.0.to_string_lossy();
+    let Ok(quoted) = shlex::try_quote(&path) else {
+        warn!(
+            "Could not run autoexec file {}: the path contains a nul byte",
+            path
+        );
+        return;
+    };
+    let sender = commands
+        .spawn((Name::new("Autoexec command sender"), AutoexecCommandSender))
+        .id();
+    buf_input.send(CommandBufInput::new(sender, format!("exec {}", quoted)));
+}
+
+fn respond_autoexec(
+    mut resps: EventReader<CommandResponse>,
+    sender: Query<Entity, With<AutoexecCommandSender>>,
+) {
+    let Ok(sender) = sender.get_single() else {
+        return;
+    };
+    for resp in resps.iter().filter(|r| r.target == sender) {
+        match resp.outcome {
+            Outcome::Ok => info!("{}", resp.message),
+            Outcome::Err => error!("{}", resp.message),
+        }
+    }
+}
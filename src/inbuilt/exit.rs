@@ -5,7 +5,7 @@ use crate::{AppCommand, QueuedCommands};
 
 /// Immediately exits the application.
 #[derive(clap::Parser, AppCommand)]
-#[command(name = "exit")]
+#[command(name = "exit", visible_aliases = ["quit", "q"])]
 pub struct Exit;
 
 pub fn exit(mut queue: QueuedCommands<Exit>, mut app_exit: EventWriter<AppExit>) {
@@ -1,25 +1,57 @@
-use bevy::prelude::ResMut;
+use bevy::prelude::{Res, ResMut};
 
-use crate::{self as bevy_commands, respond_err, CommandMetaMap};
+use crate::plugin::no_such_command;
+use crate::{
+    self as bevy_commands, respond_ok, CommandAliases, CommandMetaMap, CvarMetaMap, UserAliases,
+};
 use crate::{AppCommand, CommandResponder, QueuedCommands};
 
-/// Provides usage information on registered commands.
+/// Provides usage information on registered commands and cvars.
 #[derive(clap::Parser, AppCommand)]
-#[command(name = "help")]
+#[command(name = "help", visible_alias = "?")]
 pub struct Help {
-    /// The command to view help information for.
+    /// The command or cvar to view help information for.
     pub query: Option<String>,
 }
 
-pub fn help(mut queue: QueuedCommands<Help>, mut command_meta: ResMut<CommandMetaMap>) {
+pub fn help(
+    mut queue: QueuedCommands<Help>,
+    mut command_meta: ResMut<CommandMetaMap>,
+    aliases: Res<CommandAliases>,
+    user_aliases: Res<UserAliases>,
+    cvar_meta: Res<CvarMetaMap>,
+) {
     queue.consume(|mut ctx| match &ctx.data.query {
-        Some(query) => match command_meta.0.get_mut(query.as_str()) {
+        Some(query) => match command_meta.0.get_mut(aliases.resolve(query)) {
             Some(command) => {
                 for line in command.render_long_help().to_string().lines() {
                     ctx.ok(line);
                 }
             }
-            None => respond_err!(ctx, "No such command: {}", query),
+            None => match cvar_meta.0.get(query) {
+                Some(meta) => {
+                    if let Some(description) = &meta.description {
+                        ctx.ok(description.as_str());
+                    }
+                    respond_ok!(
+                        ctx,
+                        "{} = {} (default {})",
+                        query,
+                        meta.value,
+                        meta.default_value
+                    );
+                    if !meta.possible_values.is_empty() {
+                        respond_ok!(ctx, "Possible values: {}", meta.possible_values.join(", "));
+                    }
+                }
+                None => ctx.err(no_such_command(
+                    query,
+                    &command_meta,
+                    &aliases,
+                    &user_aliases,
+                    &cvar_meta,
+                )),
+            },
         },
         None => {
             let longest_name_len = command_meta
@@ -35,7 +67,32 @@ pub fn help(mut queue: QueuedCommands<Help>, mut command_meta: ResMut<CommandMet
                     || format!("  {}{}", name, indent),
                     |about| format!("  {}{} - {}", name, indent, about),
                 );
-                ctx.ok(message);
+                let mut command_aliases = aliases.aliases_of(name).collect::<Vec<_>>();
+                if command_aliases.is_empty() {
+                    ctx.ok(message);
+                } else {
+                    command_aliases.sort_unstable();
+                    ctx.ok(format!(
+                        "{} (aliases: {})",
+                        message,
+                        command_aliases.join(", ")
+                    ));
+                }
+            }
+
+            if cvar_meta.0.is_empty() {
+                return;
+            }
+            let mut cvars = cvar_meta.0.iter().collect::<Vec<_>>();
+            cvars.sort_unstable_by_key(|(name, _)| name.as_str());
+            let longest_name_len = cvars.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            ctx.ok("Available cvars:");
+            for (name, meta) in cvars {
+                let indent = " ".repeat(longest_name_len - name.len());
+                match &meta.description {
+                    Some(description) => respond_ok!(ctx, "  {}{} - {}", name, indent, description),
+                    None => respond_ok!(ctx, "  {}{}", name, indent),
+                }
             }
         }
     });
@@ -1,17 +1,38 @@
+pub mod alias;
+pub mod cvar;
 pub mod echo;
+pub mod exec;
 pub mod exit;
 pub mod help;
 
 use bevy::prelude::*;
 
+use crate::dispatch::complete_commands;
 use crate::plugin::AddAppCommand;
 
+pub use exec::AutoexecPlugin;
+
 pub struct InbuiltCommandsPlugin;
 
 impl Plugin for InbuiltCommandsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_app_command::<echo::Echo, _>(echo::echo)
-            .add_app_command::<exit::Exit, _>(exit::exit)
-            .add_app_command::<help::Help, _>(help::help);
+        app.add_app_command_with_permission::<alias::Alias, _>("commands.alias", alias::alias)
+            .add_app_command_with_permission::<alias::Unalias, _>(
+                "commands.unalias",
+                alias::unalias,
+            )
+            .add_app_command_with_permission::<cvar::Set, _>("commands.set", cvar::set)
+            .add_app_command::<cvar::Get, _>(cvar::get)
+            .add_app_command_with_permission::<cvar::Reset, _>("commands.reset", cvar::reset)
+            .add_app_command::<cvar::CvarList, _>(cvar::cvarlist)
+            .add_app_command::<echo::Echo, _>(echo::echo)
+            .add_app_command_with_permission::<exec::Exec, _>("commands.exec", exec::exec)
+            .add_app_command_with_permission::<exit::Exit, _>("commands.exit", exit::exit)
+            .add_app_command::<help::Help, _>(help::help)
+            .init_resource::<exec::ExecScripts>()
+            .add_systems(
+                PostUpdate,
+                exec::advance_exec_scripts.after(complete_commands),
+            );
     }
 }
@@ -3,28 +3,45 @@
 #![warn(clippy::nursery)]
 //#![warn(clippy::cargo)]
 
+pub mod chain;
+pub mod completion;
+pub mod cvar;
 pub mod dispatch;
 #[cfg(feature = "egui")]
 pub mod egui;
 #[cfg(feature = "inbuilt")]
 pub mod inbuilt;
 pub mod macros;
+pub mod permission;
+pub mod persist;
 pub mod plugin;
 #[cfg(feature = "stdio")]
 pub mod stdio;
+pub mod syntax;
+#[cfg(feature = "tcp")]
+pub mod tcp;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 #[cfg(feature = "derive")]
 pub use bevy_commands_derive::AppCommand;
 pub use clap;
 
+pub use crate::chain::CommandChains;
+pub use crate::completion::{Completion, CompletionData, SharedCompletionData};
+pub use crate::cvar::{AddCvar, Cvar, CvarChanged, CvarMetaMap};
 pub use crate::dispatch::{
-    AppCommand, CommandContext, CommandDispatch, CommandResponder, CommandResponse, Outcome,
-    QueuedCommands,
+    AppCommand, CommandCompleted, CommandContext, CommandDispatch, CommandFinished, CommandId,
+    CommandResponder, CommandResponse, Outcome, QueuedCommands, ResponseHandle,
 };
 #[cfg(feature = "inbuilt")]
 pub use crate::inbuilt::InbuiltCommandsPlugin;
+pub use crate::permission::CommandPermissions;
+pub use crate::persist::{PersistFormat, PersistPlugin};
 pub use crate::plugin::{
-    AddAppCommand, CommandBufInput, CommandMetaMap, CommandSet, CommandsPlugin,
+    AddAppCommand, CommandAliases, CommandBufInput, CommandMetaMap, CommandSet, CommandsPlugin,
+    UserAliases,
 };
+pub use crate::syntax::{SignatureHint, SyntaxKind, SyntaxSpan};
 
 pub const DEFAULT_PROMPT: &str = "> ";
@@ -19,3 +19,76 @@ macro_rules! respond_err {
         }
     };
 }
+
+/// Defines a [`Cvar`](crate::cvar::Cvar) stored in a newtype resource, using the doc comment as
+/// its description.
+///
+/// Prefixing the definition with `archive` marks the cvar as
+/// [archived](crate::cvar::Cvar::archive).
+///
+/// ```ignore
+/// cvar! {
+///     /// Strength of gravity, in m/s^2.
+///     pub Gravity("gravity"): f32 = 9.81
+/// }
+///
+/// cvar! {
+///     /// Field of view, in degrees.
+///     archive pub Fov("fov"): f32 = 90.0
+/// }
+///
+/// app.add_cvar::<Gravity>().add_cvar::<Fov>();
+/// ```
+#[macro_export]
+macro_rules! cvar {
+    ($(#[doc = $doc:literal])* archive $vis:vis $ident:ident($name:literal): $ty:ty = $default:expr $(;)?) => {
+        $crate::cvar! { @impl true; $(#[doc = $doc])* $vis $ident($name): $ty = $default }
+    };
+    ($(#[doc = $doc:literal])* $vis:vis $ident:ident($name:literal): $ty:ty = $default:expr $(;)?) => {
+        $crate::cvar! { @impl false; $(#[doc = $doc])* $vis $ident($name): $ty = $default }
+    };
+    (@impl $archive:literal; $(#[doc = $doc:literal])* $vis:vis $ident:ident($name:literal): $ty:ty = $default:expr) => {
+        $(#[doc = $doc])*
+        #[derive(Debug, Clone, bevy::prelude::Resource)]
+        $vis struct $ident(pub $ty);
+
+        impl ::std::default::Default for $ident {
+            fn default() -> Self {
+                Self($default)
+            }
+        }
+
+        impl $crate::cvar::Cvar for $ident {
+            type Value = $ty;
+
+            fn name() -> &'static str {
+                $name
+            }
+
+            fn description() -> ::std::option::Option<&'static str> {
+                let description = concat!($($doc, "\n"),*).trim();
+                (!description.is_empty()).then_some(description)
+            }
+
+            fn archive() -> bool {
+                $archive
+            }
+
+            fn value_parser() -> $crate::clap::builder::ValueParser {
+                $crate::clap::value_parser!($ty).into()
+            }
+
+            fn default_value() -> $ty {
+                $default
+            }
+
+            fn get(&self) -> &$ty {
+                &self.0
+            }
+
+            fn set(&mut self, value: $ty) {
+                self.0 = value;
+            }
+        }
+    };
+}
@@ -0,0 +1,93 @@
+use bevy::{prelude::*, utils::HashSet};
+
+/// Permission nodes granted to a command sender.
+///
+/// Nodes are dot-separated, such as `admin.exit`. A granted node ending in `*` also grants all
+/// nodes under it, so `admin.*` grants `admin.exit`, and `*` grants every node.
+///
+/// Senders without this component are not restricted, and may run any command.
+#[derive(Component, Debug, Clone, Default)]
+pub struct CommandPermissions {
+    nodes: HashSet<String>,
+}
+
+impl CommandPermissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, node: impl Into<String>) -> Self {
+        self.grant(node);
+        self
+    }
+
+    pub fn grant(&mut self, node: impl Into<String>) {
+        self.nodes.insert(node.into());
+    }
+
+    pub fn revoke(&mut self, node: &str) {
+        self.nodes.remove(node);
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(String::as_str)
+    }
+
+    /// Checks if `node` is granted, either directly or by a wildcard node.
+    pub fn has(&self, node: &str) -> bool {
+        self.nodes.iter().any(|granted| {
+            granted.strip_suffix('*').map_or_else(
+                || granted == node,
+                |prefix| node.starts_with(prefix) && (prefix.is_empty() || prefix.ends_with('.')),
+            )
+        })
+    }
+}
+
+pub(crate) fn is_authorized(
+    permissions: &Query<&CommandPermissions>,
+    sender: Entity,
+    node: Option<&str>,
+) -> bool {
+    match (node, permissions.get(sender)) {
+        (Some(node), Ok(permissions)) => permissions.has(node),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_nodes() {
+        let permissions = CommandPermissions::new().with("admin.exit");
+        assert!(permissions.has("admin.exit"));
+        assert!(!permissions.has("admin.exec"));
+        assert!(!permissions.has("admin"));
+        assert!(!permissions.has("admin.exit.now"));
+    }
+
+    #[test]
+    fn wildcard_nodes() {
+        let permissions = CommandPermissions::new().with("admin.*");
+        assert!(permissions.has("admin.exit"));
+        assert!(permissions.has("admin.cvars.set"));
+        assert!(!permissions.has("admin"));
+        assert!(!permissions.has("administrator.exit"));
+        assert!(!permissions.has("commands.exit"));
+
+        let permissions = CommandPermissions::new().with("*");
+        assert!(permissions.has("admin.exit"));
+        assert!(permissions.has("exit"));
+    }
+
+    #[test]
+    fn revoke() {
+        let mut permissions = CommandPermissions::new().with("a").with("b.*");
+        permissions.revoke("b.*");
+        assert!(permissions.has("a"));
+        assert!(!permissions.has("b.c"));
+        assert!(!CommandPermissions::new().has("a"));
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::cvar::{CvarMetaMap, CvarOp, CvarRequest};
+use crate::dispatch::DeferredResponses;
+use crate::{CommandId, CommandResponse, CommandSet, Outcome};
+
+/// Format of the file written by [`PersistPlugin`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PersistFormat {
+    /// A RON document containing a [`PersistedConsole`].
+    #[default]
+    Ron,
+    /// Plain text, split into a `[cvars]` section and a `[history.<frontend>]` section per
+    /// frontend.
+    ///
+    /// Cvars are written one per line as `name value`, and history entries are written one per
+    /// line as they were entered.
+    Text,
+}
+
+/// Saves archived cvars and the input history of the console frontends to a file when the app
+/// exits, and restores them when it starts.
+///
+/// Cvars are archived using [`Cvar::archive`](crate::cvar::Cvar::archive). The file is replaced
+/// atomically, and a file which cannot be read is ignored with a warning.
+pub struct PersistPlugin {
+    pub path: PathBuf,
+    pub format: PersistFormat,
+}
+
+impl Default for PersistPlugin {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("console.ron"),
+            format: PersistFormat::Ron,
+        }
+    }
+}
+
+impl Plugin for PersistPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PersistConfig {
+            path: self.path.clone(),
+            format: self.format,
+        })
+        .init_resource::<PersistedConsole>()
+        .add_systems(Startup, load_persisted)
+        .add_systems(PostStartup, restore_cvars)
+        .add_systems(Update, respond_restore_cvars.in_set(CommandSet::Response))
+        .add_systems(Last, (archive_cvars, save_persisted).chain());
+
+        // the default console is spawned during startup
+        #[cfg(feature = "egui")]
+        app.add_systems(PostStartup, restore_egui_history)
+            .add_systems(Last, archive_egui_history.before(save_persisted));
+
+        #[cfg(feature = "stdio")]
+        app.add_systems(Startup, restore_stdio_history.after(load_persisted))
+            .add_systems(Last, archive_stdio_history.before(save_persisted));
+    }
+}
+
+#[derive(Resource)]
+struct PersistConfig {
+    path: PathBuf,
+    format: PersistFormat,
+}
+
+/// State which is saved between runs by [`PersistPlugin`].
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersistedConsole {
+    /// Values of archived cvars, keyed by cvar name.
+    #[serde(default)]
+    pub cvars: BTreeMap<String, String>,
+    /// Input history of each console frontend, oldest first, keyed by frontend name.
+    #[serde(default)]
+    pub history: BTreeMap<String, Vec<String>>,
+}
+
+impl PersistedConsole {
+    pub fn from_ron(input: &str) -> Result<Self, String> {
+        ron::from_str(input).map_err(|e| e.to_string())
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn from_text(input: &str) -> Result<Self, String> {
+        let mut persisted = Self::default();
+        let mut section = None;
+        for (i, line) in input.lines().enumerate() {
+            if line == "[cvars]" {
+                section = Some(None);
+                continue;
+            }
+            if let Some(frontend) = line
+                .strip_prefix("[history.")
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                persisted.history.entry(frontend.to_owned()).or_default();
+                section = Some(Some(frontend.to_owned()));
+                continue;
+            }
+            match &section {
+                None if line.trim().is_empty() => {}
+                None => return Err(format!("line {}: expected a section header", i + 1)),
+                Some(None) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+                    persisted.cvars.insert(name.to_owned(), value.to_owned());
+                }
+                Some(Some(frontend)) => {
+                    if let Some(history) = persisted.history.get_mut(frontend) {
+                        history.push(line.to_owned());
+                    }
+                }
+            }
+        }
+        Ok(persisted)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut output = String::from("[cvars]\n");
+        for (name, value) in &self.cvars {
+            output.push_str(&format!("{} {}\n", name, value));
+        }
+        for (frontend, history) in &self.history {
+            output.push_str(&format!("[history.{}]\n", frontend));
+            for line in history {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+        output
+    }
+}
+
+fn load_persisted(config: Res<PersistConfig>, mut persisted: ResMut<PersistedConsole>) {
+    let input = match fs::read_to_string(&config.path) {
+        Ok(input) => input,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            debug!(
+                "No persisted console file found at {}",
+                config.path.display()
+            );
+            return;
+        }
+        Err(e) => {
+            warn!("Could not read {}: {}", config.path.display(), e);
+            return;
+        }
+    };
+    let result = match config.format {
+        PersistFormat::Ron => PersistedConsole::from_ron(&input),
+        PersistFormat::Text => PersistedConsole::from_text(&input),
+    };
+    match result {
+        Ok(loaded) => *persisted = loaded,
+        Err(e) => warn!("Could not parse {}: {}", config.path.display(), e),
+    }
+}
+
+#[derive(Component)]
+struct PersistCommandSender;
+
+fn restore_cvars(
+    mut commands: Commands,
+    persisted: Res<PersistedConsole>,
+    cvar_meta: Res<CvarMetaMap>,
+    deferred: Res<DeferredResponses>,
+    mut requests: EventWriter<CvarRequest>,
+) {
+    if persisted.cvars.is_empty() {
+        return;
+    }
+    let sender = commands
+        .spawn((Name::new("Persisted cvar sender"), PersistCommandSender))
+        .id();
+    for (name, value) in &persisted.cvars {
+        if !cvar_meta.0.get(name).is_some_and(|meta| meta.archive) {
+            debug!("Not restoring '{}', as it is not an archived cvar", name);
+            continue;
+        }
+        requests.send(CvarRequest {
+            handle: deferred.handle(sender, CommandId::next()),
+            name: name.clone(),
+            op: CvarOp::Set(value.clone()),
+        });
+    }
+}
+
+fn respond_restore_cvars(
+    mut resps: EventReader<CommandResponse>,
+    sender: Query<Entity, With<PersistCommandSender>>,
+) {
+    let Ok(sender) = sender.get_single() else {
+        return;
+    };
+    for resp in resps.iter().filter(|r| r.target == sender) {
+        match resp.outcome {
+            Outcome::Ok => debug!("Restored cvar: {}", resp.message),
+            Outcome::Err => warn!("Could not restore cvar: {}", resp.message),
+        }
+    }
+}
+
+fn archive_cvars(
+    mut exit: EventReader<AppExit>,
+    cvar_meta: Res<CvarMetaMap>,
+    mut persisted: ResMut<PersistedConsole>,
+) {
+    if exit.is_empty() {
+        return;
+    }
+    exit.clear();
+    // values of cvars which are not registered in this run are kept
+    for (name, meta) in cvar_meta.0.iter().filter(|(_, meta)| meta.archive) {
+        persisted.cvars.insert(name.clone(), meta.value.clone());
+    }
+}
+
+fn save_persisted(
+    mut exit: EventReader<AppExit>,
+    config: Res<PersistConfig>,
+    persisted: Res<PersistedConsole>,
+) {
+    if exit.is_empty() {
+        return;
+    }
+    exit.clear();
+
+    let output = match config.format {
+        PersistFormat::Ron => match persisted.to_ron() {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Could not serialize persisted console: {}", e);
+                return;
+            }
+        },
+        PersistFormat::Text => persisted.to_text(),
+    };
+    match write_atomic(&config.path, output.as_bytes()) {
+        Ok(()) => debug!("Saved persisted console to {}", config.path.display()),
+        Err(e) => warn!("Could not write {}: {}", config.path.display(), e),
+    }
+}
+
+/// Writes to a temporary file next to `path`, then moves it over `path`, so that `path` is
+/// never left partially written.
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(feature = "egui")]
+const EGUI_HISTORY: &str = "egui";
+
+#[cfg(feature = "egui")]
+fn restore_egui_history(
+    persisted: Res<PersistedConsole>,
+    config: Option<Res<crate::egui::ConsoleUiConfig>>,
+    mut consoles: Query<&mut crate::egui::ConsoleUiState, With<crate::egui::DefaultConsoleUi>>,
+) {
+    let (Some(config), Some(history)) = (config, persisted.history.get(EGUI_HISTORY)) else {
+        return;
+    };
+    for mut state in &mut consoles {
+        state.set_history(history.iter().rev().take(config.history_cap).cloned());
+    }
+}
+
+#[cfg(feature = "egui")]
+fn archive_egui_history(
+    mut exit: EventReader<AppExit>,
+    consoles: Query<&crate::egui::ConsoleUiState, With<crate::egui::DefaultConsoleUi>>,
+    mut persisted: ResMut<PersistedConsole>,
+) {
+    if exit.is_empty() {
+        return;
+    }
+    exit.clear();
+    let Ok(state) = consoles.get_single() else {
+        return;
+    };
+    let mut history = state.history().map(str::to_owned).collect::<Vec<_>>();
+    history.reverse();
+    persisted.history.insert(EGUI_HISTORY.to_owned(), history);
+}
+
+#[cfg(feature = "stdio")]
+const STDIO_HISTORY: &str = "stdio";
+
+#[cfg(feature = "stdio")]
+fn restore_stdio_history(
+    persisted: Res<PersistedConsole>,
+    history: Option<ResMut<crate::stdio::StdioHistory>>,
+) {
+    let (Some(mut history), Some(entries)) = (history, persisted.history.get(STDIO_HISTORY)) else {
+        return;
+    };
+    for entry in entries {
+        history.push(entry.clone());
+    }
+}
+
+#[cfg(feature = "stdio")]
+fn archive_stdio_history(
+    mut exit: EventReader<AppExit>,
+    history: Option<Res<crate::stdio::StdioHistory>>,
+    mut persisted: ResMut<PersistedConsole>,
+) {
+    if exit.is_empty() {
+        return;
+    }
+    exit.clear();
+    let Some(history) = history else {
+        return;
+    };
+    persisted
+        .history
+        .insert(STDIO_HISTORY.to_owned(), history.entries().to_vec());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn persisted() -> PersistedConsole {
+        let mut persisted = PersistedConsole::default();
+        persisted
+            .cvars
+            .insert("gravity".to_owned(), "0 -9.8 0".to_owned());
+        persisted.cvars.insert("name".to_owned(), String::new());
+        persisted.history.insert(
+            "egui".to_owned(),
+            vec!["echo \"a; b\"".to_owned(), "set gravity 1".to_owned()],
+        );
+        persisted.history.insert("stdio".to_owned(), Vec::new());
+        persisted
+    }
+
+    fn assert_same(a: &PersistedConsole, b: &PersistedConsole) {
+        assert_eq!(a.cvars, b.cvars);
+        assert_eq!(a.history, b.history);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let text = persisted().to_text();
+        assert_eq!(
+            text,
+            "[cvars]\ngravity 0 -9.8 0\nname \n[history.egui]\necho \"a; b\"\nset gravity 1\n\
+             [history.stdio]\n"
+        );
+        assert_same(&PersistedConsole::from_text(&text).unwrap(), &persisted());
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let ron = persisted().to_ron().unwrap();
+        assert_same(&PersistedConsole::from_ron(&ron).unwrap(), &persisted());
+    }
+
+    #[test]
+    fn text_requires_section() {
+        assert!(PersistedConsole::from_text("gravity 1\n").is_err());
+        let persisted = PersistedConsole::from_text("\n[cvars]\n\ngravity 1\n").unwrap();
+        assert_eq!(persisted.cvars["gravity"], "1");
+        assert!(persisted.history.is_empty());
+    }
+}
@@ -1,17 +1,39 @@
+use std::collections::VecDeque;
+
 use bevy::{prelude::*, utils::HashMap};
 
-use crate::{AppCommand, CommandDispatch, CommandResponse};
+use crate::{
+    chain::{
+        advance_command_chains, split_chain, validate_chain, ChainOp, CommandChain, CommandChains,
+    },
+    completion::{update_completion_data, SharedCompletionData},
+    cvar::{CvarChanged, CvarMetaMap, CvarRequest},
+    dispatch::{complete_commands, forward_deferred_responses, DeferredResponses, PendingOutcomes},
+    permission::{is_authorized, CommandPermissions},
+    AppCommand, CommandCompleted, CommandDispatch, CommandFinished, CommandId, CommandResponse,
+};
 
 pub struct CommandsPlugin;
 
 impl Plugin for CommandsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(CommandMetaMap(HashMap::default()))
+            .insert_resource(CommandAliases(HashMap::default()))
+            .insert_resource(UserAliases(HashMap::default()))
             .insert_resource(RespondToInvalidCommand(true))
+            .init_resource::<SharedCompletionData>()
+            .init_resource::<DeferredResponses>()
+            .init_resource::<PendingOutcomes>()
+            .init_resource::<CommandChains>()
+            .init_resource::<CvarMetaMap>()
             .add_event::<CommandBufInput>()
             .add_event::<CommandArgsInput>()
             .add_event::<CommandResponse>()
+            .add_event::<CommandFinished>()
+            .add_event::<CommandCompleted>()
             .add_event::<InvalidCommandInput>()
+            .add_event::<CvarRequest>()
+            .add_event::<CvarChanged>()
             .configure_sets(
                 Update,
                 (
@@ -29,6 +51,21 @@ impl Plugin for CommandsPlugin {
                 ),
             )
             .add_systems(Update, (parse_command_bufs).before(CommandSet::Dispatch))
+            .add_systems(
+                Update,
+                (update_completion_data).run_if(
+                    resource_changed::<CommandMetaMap>()
+                        .or_else(resource_changed::<CommandAliases>())
+                        .or_else(resource_changed::<UserAliases>())
+                        .or_else(resource_changed::<CvarMetaMap>()),
+                ),
+            )
+            .add_systems(
+                Update,
+                (forward_deferred_responses)
+                    .after(CommandSet::Process)
+                    .before(CommandSet::Response),
+            )
             .add_systems(
                 Update,
                 (mark_invalid_commands)
@@ -40,6 +77,10 @@ impl Plugin for CommandsPlugin {
                 (invalid_command_response)
                     .after(CommandSet::Response)
                     .run_if(respond_to_invalid_command),
+            )
+            .add_systems(
+                PostUpdate,
+                (complete_commands, advance_command_chains).chain(),
             );
     }
 }
@@ -69,9 +110,66 @@ fn respond_to_invalid_command(res: Res<RespondToInvalidCommand>) -> bool {
 #[derive(Resource)]
 pub struct CommandMetaMap(pub HashMap<&'static str, clap::Command>);
 
+/// Maps alternative names of commands to the name of the command they refer to.
+///
+/// This holds both the aliases declared on a command's [`clap::Command`], and those added with
+/// [`AddAppCommand::add_app_command_alias`].
+#[derive(Resource)]
+pub struct CommandAliases(pub HashMap<String, &'static str>);
+
+impl CommandAliases {
+    /// Gets the name of the command which `name` refers to, which is `name` itself if it is not
+    /// an alias.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.0.get(name).copied().unwrap_or(name)
+    }
+
+    /// Gets all aliases of the command with the given name.
+    pub fn aliases_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.0
+            .iter()
+            .filter(move |(_, target)| **target == name)
+            .map(|(alias, _)| alias.as_str())
+    }
+}
+
+/// Aliases defined at runtime, which expand to a command line.
+///
+/// When the first word of a command buffer is one of these aliases, it is replaced with the
+/// alias' expansion before the buffer is parsed.
+#[derive(Resource)]
+pub struct UserAliases(pub HashMap<String, String>);
+
+impl UserAliases {
+    /// Expands the alias at the start of a single command, returning the alias' name and the
+    /// expanded buffer, or [`None`] if the first word is not an alias.
+    ///
+    /// The expansion may itself start with an alias or be a chain of commands, which is expanded
+    /// separately.
+    pub fn expand_once(&self, buf: &str) -> Option<(&str, String)> {
+        let trimmed = buf.trim_start();
+        let (name, rest) = trimmed
+            .find(char::is_whitespace)
+            .map_or((trimmed, ""), |i| trimmed.split_at(i));
+        let (name, expansion) = self.0.get_key_value(name)?;
+        Some((name, format!("{}{}", expansion, rest)))
+    }
+}
+
 pub trait AddAppCommand {
     fn add_app_command<C: AppCommand, M>(&mut self, system: impl IntoSystemConfigs<M>)
         -> &mut Self;
+
+    /// Adds a command which can only be run by senders granted the `permission` node in their
+    /// [`CommandPermissions`].
+    fn add_app_command_with_permission<C: AppCommand, M>(
+        &mut self,
+        permission: impl Into<String>,
+        system: impl IntoSystemConfigs<M>,
+    ) -> &mut Self;
+
+    /// Adds an alternative name which can be used to run a command.
+    fn add_app_command_alias<C: AppCommand>(&mut self, alias: impl Into<String>) -> &mut Self;
 }
 
 impl AddAppCommand for App {
@@ -79,59 +177,140 @@ impl AddAppCommand for App {
         &mut self,
         systems: impl IntoSystemConfigs<M>,
     ) -> &mut Self {
-        fn command<C: AppCommand>() -> clap::Command {
-            C::command().no_binary_name(true)
+        add_app_command::<C, M>(self, None, systems)
+    }
+
+    fn add_app_command_with_permission<C: AppCommand, M>(
+        &mut self,
+        permission: impl Into<String>,
+        systems: impl IntoSystemConfigs<M>,
+    ) -> &mut Self {
+        add_app_command::<C, M>(self, Some(permission.into()), systems)
+    }
+
+    fn add_app_command_alias<C: AppCommand>(&mut self, alias: impl Into<String>) -> &mut Self {
+        let alias = alias.into();
+        let setup_alias = move |mut aliases: ResMut<CommandAliases>| {
+            insert_alias(&mut aliases, alias.clone(), C::name());
+        };
+
+        self.add_systems(Startup, setup_alias)
+    }
+}
+
+fn insert_alias(aliases: &mut CommandAliases, alias: String, name: &'static str) {
+    if let Some(old) = aliases.0.insert(alias.clone(), name) {
+        if old != name {
+            warn!(
+                "Alias '{}' already refers to '{}', overwriting with '{}'",
+                alias, old, name
+            );
         }
+    }
+}
+
+fn add_app_command<C: AppCommand, M>(
+    app: &mut App,
+    permission: Option<String>,
+    systems: impl IntoSystemConfigs<M>,
+) -> &mut App {
+    fn command<C: AppCommand>() -> clap::Command {
+        C::command().no_binary_name(true)
+    }
 
-        let setup_command_meta = move |mut command_meta: ResMut<CommandMetaMap>| {
-            if command_meta.0.insert(C::name(), command::<C>()).is_some() {
+    let setup_command_meta =
+        move |mut command_meta: ResMut<CommandMetaMap>, mut aliases: ResMut<CommandAliases>| {
+            let command = command::<C>();
+            for alias in command.get_all_aliases() {
+                insert_alias(&mut aliases, alias.to_owned(), C::name());
+            }
+            if command_meta.0.insert(C::name(), command).is_some() {
                 warn!("Command '{}' already exists, overwriting", C::name());
             }
         };
 
-        let dispatch_command =
-            move |mut input: EventReader<CommandArgsInput>,
-                  mut dispatch: EventWriter<CommandDispatch<C>>,
-                  mut resps: EventWriter<CommandResponse>| {
-                for input in input.iter().filter(|input| input.name == C::name()) {
-                    debug!("Dispatching '{}' sent by {:?}", input.name, input.sender);
-                    match command::<C>()
-                        .clone()
-                        .try_get_matches_from(input.args.iter())
-                        .and_then(|matches| C::from_arg_matches(&matches))
-                    {
-                        Ok(data) => dispatch.send(CommandDispatch {
-                            sender: input.sender,
-                            data,
-                        }),
-                        Err(e) => {
-                            resps.send_batch(
-                                e.render()
-                                    .to_string()
-                                    .lines()
-                                    .map(|s| CommandResponse::err(input.sender, s)),
-                            );
-                        }
+    let dispatch_command =
+        move |mut input: EventReader<CommandArgsInput>,
+              mut dispatch: EventWriter<CommandDispatch<C>>,
+              mut resps: EventWriter<CommandResponse>,
+              mut finished: EventWriter<CommandFinished>,
+              permissions: Query<&CommandPermissions>| {
+            for input in input.iter().filter(|input| input.name == C::name()) {
+                if !is_authorized(&permissions, input.sender, permission.as_deref()) {
+                    debug!(
+                        "Rejecting '{}' {} sent by {:?}: missing permission",
+                        input.name, input.id, input.sender
+                    );
+                    resps.send(CommandResponse::err(
+                        input.sender,
+                        input.id,
+                        format!("You do not have permission to use {}", input.name),
+                    ));
+                    finished.send(CommandFinished {
+                        target: input.sender,
+                        id: input.id,
+                    });
+                    continue;
+                }
+
+                debug!(
+                    "Dispatching '{}' {} sent by {:?}",
+                    input.name, input.id, input.sender
+                );
+                match command::<C>()
+                    .clone()
+                    .try_get_matches_from(input.args.iter())
+                    .and_then(|matches| C::from_arg_matches(&matches))
+                {
+                    Ok(data) => dispatch.send(CommandDispatch {
+                        sender: input.sender,
+                        id: input.id,
+                        data,
+                    }),
+                    Err(e) => {
+                        resps.send_batch(
+                            e.render()
+                                .to_string()
+                                .lines()
+                                .map(|s| CommandResponse::err(input.sender, input.id, s)),
+                        );
+                        finished.send(CommandFinished {
+                            target: input.sender,
+                            id: input.id,
+                        });
                     }
                 }
-            };
+            }
+        };
 
-        self.add_event::<CommandDispatch<C>>()
-            .add_systems(Startup, setup_command_meta)
-            .add_systems(Update, (dispatch_command).in_set(CommandSet::Dispatch))
-            .add_systems(Update, systems.in_set(CommandSet::Process))
-    }
+    app.add_event::<CommandDispatch<C>>()
+        .add_systems(Startup, setup_command_meta)
+        .add_systems(Update, (dispatch_command).in_set(CommandSet::Dispatch))
+        .add_systems(Update, systems.in_set(CommandSet::Process))
 }
 
 #[derive(Event)]
 pub struct CommandBufInput {
     pub sender: Entity,
+    pub id: CommandId,
     pub buf: String,
 }
 
+impl CommandBufInput {
+    /// Creates an input with a newly allocated [`CommandId`].
+    pub fn new(sender: Entity, buf: impl Into<String>) -> Self {
+        Self {
+            sender,
+            id: CommandId::next(),
+            buf: buf.into(),
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct CommandArgsInput {
     pub sender: Entity,
+    pub id: CommandId,
     pub name: String,
     pub args: Vec<String>,
 }
@@ -139,31 +318,148 @@ pub struct CommandArgsInput {
 #[derive(Event)]
 pub struct InvalidCommandInput {
     pub target: Entity,
+    pub id: CommandId,
     pub name: String,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn parse_command_bufs(
     mut buf_input: EventReader<CommandBufInput>,
     mut args_input: EventWriter<CommandArgsInput>,
+    mut resps: EventWriter<CommandResponse>,
+    mut finished: EventWriter<CommandFinished>,
+    mut chains: ResMut<CommandChains>,
+    command_meta: Res<CommandMetaMap>,
+    aliases: Res<CommandAliases>,
+    user_aliases: Res<UserAliases>,
+    cvar_meta: Res<CvarMetaMap>,
 ) {
-    args_input.send_batch(buf_input.iter().filter_map(|input| {
-        let mut args = shlex::split(&input.buf).unwrap_or_default();
+    let mut queue = buf_input
+        .iter()
+        .map(|input| {
+            // Later commands of a chain are still part of the aliases which expanded to the chain.
+            let expanded = chains
+                .0
+                .get(&input.id)
+                .map_or_else(Vec::new, |chain| chain.expanded.clone());
+            (input.sender, input.id, input.buf.clone(), expanded)
+        })
+        .collect::<VecDeque<_>>();
+
+    while let Some((sender, id, buf, mut expanded)) = queue.pop_front() {
+        let mut fail = |message: String| {
+            resps.send(CommandResponse::err(sender, id, message));
+            finished.send(CommandFinished { target: sender, id });
+        };
+
+        let segments = match validate_chain(split_chain(&buf)) {
+            Ok(segments) => segments,
+            Err(e) => {
+                fail(e);
+                continue;
+            }
+        };
+        if segments.len() > 1 {
+            let first = CommandId::next();
+            queue.push_back((sender, first, segments[0].buf.to_owned(), expanded.clone()));
+            chains.0.insert(
+                first,
+                CommandChain {
+                    sender,
+                    id,
+                    expanded,
+                    remaining: segments[1..]
+                        .iter()
+                        .map(|segment| {
+                            (segment.op.unwrap_or(ChainOp::Then), segment.buf.to_owned())
+                        })
+                        .collect(),
+                },
+            );
+            continue;
+        }
+        let Some(segment) = segments.first() else {
+            finished.send(CommandFinished { target: sender, id });
+            continue;
+        };
+        let buf = segment.buf;
+
+        // the expansion is parsed as its own buffer, so a chain in it is only part of this alias
+        if let Some((name, expansion)) = user_aliases.expand_once(buf) {
+            if expanded.iter().any(|expanded| expanded == name) {
+                fail(format!("Alias '{}' expands recursively", name));
+                continue;
+            }
+            expanded.push(name.to_owned());
+            queue.push_front((sender, id, expansion, expanded));
+            continue;
+        }
+
+        let Some(mut args) = shlex::split(buf) else {
+            fail(format!("Could not parse command: {}", buf));
+            continue;
+        };
         if args.is_empty() {
-            return None;
+            finished.send(CommandFinished { target: sender, id });
+            continue;
         }
-        let name = args.remove(0);
-        Some(CommandArgsInput {
-            sender: input.sender,
+        let mut name = args.remove(0);
+        if !command_meta.0.contains_key(name.as_str()) {
+            name = aliases.resolve(&name).to_owned();
+        }
+        // cvar shorthand relies on the inbuilt `set` and `get` commands
+        if !command_meta.0.contains_key(name.as_str()) && command_meta.0.contains_key("set") {
+            if let Some((cvar_name, cvar_args)) = cvar_shorthand(&name, &args, &cvar_meta) {
+                name = cvar_name;
+                args = cvar_args;
+            }
+        }
+        args_input.send(CommandArgsInput {
+            sender,
+            id,
             name,
             args,
-        })
-    }));
+        });
+    }
+}
+
+/// Rewrites `name value`, `name = value` and `name=value` into `set name value`, and a lone
+/// `name` into `get name`, if `name` is a cvar.
+fn cvar_shorthand(
+    name: &str,
+    args: &[String],
+    cvar_meta: &CvarMetaMap,
+) -> Option<(String, Vec<String>)> {
+    let (cvar, first) = name
+        .split_once('=')
+        .map_or((name, None), |(cvar, value)| (cvar, Some(value)));
+    if !cvar_meta.0.contains_key(cvar) {
+        return None;
+    }
+
+    let mut values = first
+        .into_iter()
+        .chain(args.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    if first.is_none() {
+        if let Some(value) = values.first_mut() {
+            *value = value.strip_prefix('=').unwrap_or(value);
+        }
+    }
+    values.retain(|value| !value.is_empty());
+
+    if values.is_empty() {
+        Some(("get".to_owned(), vec![cvar.to_owned()]))
+    } else {
+        Some(("set".to_owned(), vec![cvar.to_owned(), values.join(" ")]))
+    }
 }
 
 fn mark_invalid_commands(
     mut input: EventReader<CommandArgsInput>,
     command_meta: Res<CommandMetaMap>,
     mut invalid: EventWriter<InvalidCommandInput>,
+    mut finished: EventWriter<CommandFinished>,
 ) {
     for input in input
         .iter()
@@ -175,19 +471,338 @@ fn mark_invalid_commands(
         );
         invalid.send(InvalidCommandInput {
             target: input.sender,
+            id: input.id,
             name: input.name.clone(),
-        })
+        });
+        finished.send(CommandFinished {
+            target: input.sender,
+            id: input.id,
+        });
     }
 }
 
 fn invalid_command_response(
     mut events: EventReader<InvalidCommandInput>,
     mut resps: EventWriter<CommandResponse>,
+    command_meta: Res<CommandMetaMap>,
+    aliases: Res<CommandAliases>,
+    user_aliases: Res<UserAliases>,
+    cvar_meta: Res<CvarMetaMap>,
 ) {
     for event in events.iter() {
         resps.send(CommandResponse::err(
             event.target,
-            format!("No such command: {}", event.name),
+            event.id,
+            no_such_command(
+                &event.name,
+                &command_meta,
+                &aliases,
+                &user_aliases,
+                &cvar_meta,
+            ),
         ));
     }
 }
+
+/// Creates the message for an unknown command name, suggesting similarly named commands,
+/// aliases and cvars.
+pub fn no_such_command(
+    name: &str,
+    command_meta: &CommandMetaMap,
+    aliases: &CommandAliases,
+    user_aliases: &UserAliases,
+    cvar_meta: &CvarMetaMap,
+) -> String {
+    let candidates = command_meta
+        .0
+        .keys()
+        .copied()
+        .chain(aliases.0.keys().map(String::as_str))
+        .chain(user_aliases.0.keys().map(String::as_str))
+        .chain(cvar_meta.0.keys().map(String::as_str));
+    let suggestions = suggest_names(name, candidates);
+    if suggestions.is_empty() {
+        format!("No such command: {}", name)
+    } else {
+        format!(
+            "No such command: {}. Did you mean: {}?",
+            name,
+            suggestions.join(", ")
+        )
+    }
+}
+
+/// Finds the candidates closest to `name` by edit distance, to suggest as corrections to typos.
+///
+/// A candidate is only suggested if some of it is left unchanged, so short names aren't
+/// suggested for every other short name.
+pub fn suggest_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    const MAX_SUGGESTIONS: usize = 3;
+
+    let name_len = name.chars().count();
+    let max_distance = (name_len / 3).max(1);
+    let mut suggestions = candidates
+        .into_iter()
+        .map(|candidate| (strsim::damerau_levenshtein(name, candidate), candidate))
+        .filter(|(distance, candidate)| {
+            *distance <= max_distance && *distance < name_len.min(candidate.chars().count())
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort_unstable();
+    suggestions.dedup_by_key(|(_, candidate)| *candidate);
+    suggestions
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cvar::CvarMeta, Outcome};
+
+    fn user_aliases(aliases: &[(&str, &str)]) -> UserAliases {
+        UserAliases(
+            aliases
+                .iter()
+                .map(|(name, expansion)| ((*name).to_owned(), (*expansion).to_owned()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn expand_user_alias() {
+        let aliases = user_aliases(&[("hi", "echo hi"), ("greet", "hi there")]);
+        assert_eq!(
+            aliases.expand_once("hi"),
+            Some(("hi", "echo hi".to_owned()))
+        );
+        assert_eq!(
+            aliases.expand_once("  hi there"),
+            Some(("hi", "echo hi there".to_owned()))
+        );
+        // only the first alias is expanded
+        assert_eq!(
+            aliases.expand_once("greet you"),
+            Some(("greet", "hi there you".to_owned()))
+        );
+        assert_eq!(aliases.expand_once("echo hi"), None);
+        assert_eq!(aliases.expand_once("hit"), None);
+        assert_eq!(aliases.expand_once(""), None);
+    }
+
+    #[test]
+    fn suggest_similar_names() {
+        let names = ["echo", "exit", "exec", "help", "?", "q"];
+        assert_eq!(suggest_names("ecko", names), ["echo"]);
+        assert_eq!(suggest_names("exot", names), ["exit"]);
+        assert_eq!(suggest_names("hlep", names), ["help"]);
+        assert!(suggest_names("nothing", names).is_empty());
+    }
+
+    #[test]
+    fn suggest_no_short_names() {
+        let names = ["?", "q", "go"];
+        assert!(suggest_names(";", names).is_empty());
+        assert!(suggest_names("x", names).is_empty());
+        assert!(suggest_names("ab", names).is_empty());
+        assert_eq!(suggest_names("gp", names), ["go"]);
+    }
+
+    /// The commands run for a command buffer, and the errors and outcome of running it.
+    #[derive(Debug, Default)]
+    struct Run {
+        commands: Vec<String>,
+        errors: Vec<String>,
+        outcome: Option<Outcome>,
+    }
+
+    /// Sends `buf` to an app with only [`CommandsPlugin`], in which `echo` and `fail` are known
+    /// commands which finish straight away, with `fail` failing.
+    fn run_buf(aliases: &[(&str, &str)], buf: &str) -> Run {
+        let mut app = App::new();
+        app.add_plugins(CommandsPlugin)
+            .insert_resource(user_aliases(aliases));
+        for name in ["echo", "fail"] {
+            let mut command_meta = app.world.resource_mut::<CommandMetaMap>();
+            command_meta.0.insert(name, clap::Command::new(name));
+        }
+        let sender = app.world.spawn_empty().id();
+        let input = CommandBufInput::new(sender, buf);
+        let id = input.id;
+        app.world.send_event(input);
+
+        let mut run = Run::default();
+        for _ in 0..10 {
+            app.update();
+            let inputs = app
+                .world
+                .resource_mut::<Events<CommandArgsInput>>()
+                .drain()
+                .collect::<Vec<_>>();
+            run.errors.extend(
+                app.world
+                    .resource_mut::<Events<CommandResponse>>()
+                    .drain()
+                    .filter(|resp| resp.target == sender)
+                    .map(|resp| resp.message.to_string()),
+            );
+            run.outcome = run.outcome.or_else(|| {
+                app.world
+                    .resource_mut::<Events<CommandCompleted>>()
+                    .drain()
+                    .find(|event| event.id == id)
+                    .map(|event| event.outcome)
+            });
+            for input in inputs {
+                if input.name == "fail" {
+                    // not sent to the sender, so that only the errors of parsing are collected
+                    let resp = CommandResponse::err(Entity::PLACEHOLDER, input.id, "failed");
+                    app.world.send_event(resp);
+                }
+                app.world.send_event(CommandFinished {
+                    target: sender,
+                    id: input.id,
+                });
+                run.commands.push(
+                    std::iter::once(input.name)
+                        .chain(input.args)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                );
+            }
+        }
+        run
+    }
+
+    #[test]
+    fn run_single_command() {
+        let run = run_buf(&[], "echo a 'b c'");
+        assert_eq!(run.commands, ["echo a b c"]);
+        assert_eq!(run.outcome, Some(Outcome::Ok));
+    }
+
+    #[test]
+    fn run_command_with_trailing_semicolon() {
+        assert_eq!(run_buf(&[], "echo x ;").commands, ["echo x"]);
+        assert_eq!(run_buf(&[], "echo a;").commands, ["echo a"]);
+
+        let run = run_buf(&[], " ; ");
+        assert!(run.commands.is_empty());
+        assert!(run.errors.is_empty());
+        assert_eq!(run.outcome, Some(Outcome::Ok));
+    }
+
+    #[test]
+    fn run_chain() {
+        let run = run_buf(&[], "echo a; fail && echo b || echo c; echo 'd;'");
+        assert_eq!(run.commands, ["echo a", "fail", "echo c", "echo d;"]);
+        assert_eq!(run.outcome, Some(Outcome::Ok));
+
+        let run = run_buf(&[], "echo a && fail");
+        assert_eq!(run.commands, ["echo a", "fail"]);
+        assert_eq!(run.outcome, Some(Outcome::Err));
+    }
+
+    #[test]
+    fn run_invalid_chain() {
+        let run = run_buf(&[], "echo a && && echo b");
+        assert!(run.commands.is_empty());
+        assert_eq!(run.errors, ["Expected a command around '&&'"]);
+        assert_eq!(run.outcome, Some(Outcome::Err));
+    }
+
+    #[test]
+    fn run_user_aliases_in_chain() {
+        let aliases = [("hi", "echo hi"), ("both", "hi; echo there")];
+        assert_eq!(
+            run_buf(&aliases, "hi ; hi").commands,
+            ["echo hi", "echo hi"]
+        );
+        assert_eq!(
+            run_buf(&aliases, "hi && hi").commands,
+            ["echo hi", "echo hi"]
+        );
+        assert_eq!(
+            run_buf(&aliases, "both; both").commands,
+            ["echo hi", "echo there", "echo hi", "echo there"]
+        );
+    }
+
+    #[test]
+    fn run_recursive_user_alias() {
+        let aliases = [
+            ("a", "a; echo b"),
+            ("c", "echo c; c"),
+            ("d", "e"),
+            ("e", "d"),
+        ];
+        let run = run_buf(&aliases, "a");
+        assert_eq!(run.commands, ["echo b"]);
+        assert_eq!(run.errors, ["Alias 'a' expands recursively"]);
+
+        let run = run_buf(&aliases, "c");
+        assert_eq!(run.commands, ["echo c"]);
+        assert_eq!(run.errors, ["Alias 'c' expands recursively"]);
+        assert_eq!(run.outcome, Some(Outcome::Err));
+
+        let run = run_buf(&aliases, "d");
+        assert_eq!(run.errors, ["Alias 'd' expands recursively"]);
+    }
+
+    fn shorthand(buf: &str) -> Option<String> {
+        let cvar_meta = CvarMetaMap(HashMap::from_iter([(
+            "gravity".to_owned(),
+            CvarMeta {
+                description: None,
+                default_value: "9.8".to_owned(),
+                value: "9.8".to_owned(),
+                possible_values: Vec::new(),
+                archive: false,
+            },
+        )]));
+        let mut args = shlex::split(buf).unwrap();
+        let name = args.remove(0);
+        cvar_shorthand(&name, &args, &cvar_meta)
+            .map(|(name, args)| format!("{} {}", name, args.join(" ")))
+    }
+
+    #[test]
+    fn cvar_shorthand_set() {
+        for buf in [
+            "gravity 1",
+            "gravity=1",
+            "gravity = 1",
+            "gravity =1",
+            "gravity= 1",
+        ] {
+            assert_eq!(
+                shorthand(buf).as_deref(),
+                Some("set gravity 1"),
+                "{:?}",
+                buf
+            );
+        }
+        assert_eq!(
+            shorthand("gravity 0 -9.8 0").as_deref(),
+            Some("set gravity 0 -9.8 0")
+        );
+    }
+
+    #[test]
+    fn cvar_shorthand_get() {
+        for buf in ["gravity", "gravity=", "gravity ="] {
+            assert_eq!(shorthand(buf).as_deref(), Some("get gravity"), "{:?}", buf);
+        }
+    }
+
+    #[test]
+    fn cvar_shorthand_unknown_cvar() {
+        assert_eq!(shorthand("gravty 1"), None);
+        assert_eq!(shorthand("speed=1"), None);
+    }
+}
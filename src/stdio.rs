@@ -1,24 +1,32 @@
+use std::borrow::Cow;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{mpsc, Mutex};
 use std::thread;
 
 use bevy::app::PluginGroupBuilder;
 use bevy::{app::AppExit, prelude::*};
-use rustyline::{error::ReadlineError, history::MemHistory, Editor};
+use rustyline::completion::{Completer, Pair};
+use rustyline::config::Configurer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::{Hint, Hinter};
+use rustyline::history::MemHistory;
+use rustyline::validate::Validator;
+use rustyline::{error::ReadlineError, Context, Editor, Helper};
 
 use crate::inbuilt::InbuiltCommandsPlugin;
 use crate::{
-    CommandBufInput, CommandResponse, CommandSet, CommandsPlugin, Outcome, DEFAULT_PROMPT,
+    CommandBufInput, CommandResponse, CommandSet, CommandsPlugin, Outcome, SharedCompletionData,
+    DEFAULT_PROMPT,
 };
 
-pub type StdioEditor = Editor<(), MemHistory>;
+pub type StdioEditor = Editor<StdioHelper, MemHistory>;
 
 pub struct StdioInputPlugin {
     editor: Mutex<Option<StdioEditor>>,
 }
 
 impl StdioInputPlugin {
-    pub fn with_editor(editor: StdioEditor) -> Self {
+    pub const fn with_editor(editor: StdioEditor) -> Self {
         Self {
             editor: Mutex::new(Some(editor)),
         }
@@ -34,20 +42,34 @@ impl StdioInputPlugin {
     }
 }
 
+impl Default for StdioInputPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Plugin for StdioInputPlugin {
     fn build(&self, app: &mut App) {
         let (tx_input, rx_input) = mpsc::channel::<StdioInput>();
-        let (tx_prompt, rx_prompt) = mpsc::channel::<String>();
+        let (tx_output, rx_output) = mpsc::channel::<StdioOutput>();
 
-        let editor = self.editor.lock().unwrap().take().unwrap();
-        thread::spawn(move || read_stdio(editor, tx_input, rx_prompt));
+        let completions = app
+            .world
+            .get_resource_or_insert_with(SharedCompletionData::default)
+            .clone();
+        let mut editor = self.editor.lock().unwrap().take().unwrap();
+        editor.set_helper(Some(StdioHelper { completions }));
+        let history = StdioHistory::new(editor.config_mut().max_history_size());
+        thread::spawn(move || read_stdio(editor, tx_input, rx_output));
 
         app.insert_resource(StdioPrompt::default())
+            .insert_resource(history)
             .insert_non_send_resource(StdioChannels {
                 rx_input,
-                tx_prompt,
+                tx_output,
             })
             .add_systems(Startup, setup_stdio_sender)
+            .add_systems(PostStartup, send_stdio_history)
             .add_systems(
                 Update,
                 (receive_stdio_input, send_stdio_prompt).in_set(CommandSet::Dispatch),
@@ -58,7 +80,7 @@ impl Plugin for StdioInputPlugin {
 
 struct StdioChannels {
     rx_input: Receiver<StdioInput>,
-    tx_prompt: Sender<String>,
+    tx_output: Sender<StdioOutput>,
 }
 
 #[derive(Resource)]
@@ -70,6 +92,109 @@ impl Default for StdioPrompt {
     }
 }
 
+/// Lines entered into the stdio editor, oldest first.
+///
+/// Entries present at [`PostStartup`] are loaded into the editor's history.
+#[derive(Resource)]
+pub struct StdioHistory {
+    entries: Vec<String>,
+    max_len: usize,
+}
+
+impl StdioHistory {
+    const fn new(max_len: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            max_len,
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Adds an entry in the same way as the editor does, ignoring blank lines and repeats of
+    /// the last entry.
+    pub fn push(&mut self, entry: String) {
+        if entry.trim().is_empty() || self.entries.last() == Some(&entry) {
+            return;
+        }
+        self.entries.push(entry);
+        if self.entries.len() > self.max_len {
+            self.entries.drain(..self.entries.len() - self.max_len);
+        }
+    }
+}
+
+/// Provides completions and hints to the stdio editor from the registered commands.
+pub struct StdioHelper {
+    completions: SharedCompletionData,
+}
+
+impl Completer for StdioHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let Ok(completions) = self.completions.0.read() else {
+            return Ok((pos, Vec::new()));
+        };
+        let (start, candidates) = completions.complete(line, pos);
+        let candidates = candidates
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.display(),
+                replacement: candidate.value,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+/// The usage of the rest of the command being typed, and the help text of the argument being
+/// typed.
+pub struct StdioHint(String);
+
+impl Hint for StdioHint {
+    fn display(&self) -> &str {
+        &self.0
+    }
+
+    // the usage is not meant to be typed out as-is
+    fn completion(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Hinter for StdioHelper {
+    type Hint = StdioHint;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<StdioHint> {
+        if pos < line.len() {
+            return None;
+        }
+        let hint = self.completions.0.read().ok()?.signature_hint(line)?;
+        Some(StdioHint(match hint.help {
+            Some(help) => format!("{}  ({})", hint.usage, help),
+            None => hint.usage,
+        }))
+    }
+}
+
+impl Highlighter for StdioHelper {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[90m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for StdioHelper {}
+
+impl Helper for StdioHelper {}
+
 #[derive(Resource)]
 pub struct StdioCommandSender(pub Entity);
 
@@ -81,6 +206,11 @@ enum StdioInput {
     Exit,
 }
 
+enum StdioOutput {
+    Prompt(String),
+    History(Vec<String>),
+}
+
 fn setup_stdio_sender(mut commands: Commands) {
     let sender = commands
         .spawn((Name::new("Stdio command sender"), DefaultStdioCommandSender))
@@ -88,11 +218,24 @@ fn setup_stdio_sender(mut commands: Commands) {
     commands.insert_resource(StdioCommandSender(sender));
 }
 
-fn read_stdio(mut editor: StdioEditor, tx_input: Sender<StdioInput>, rx_prompt: Receiver<String>) {
+fn read_stdio(
+    mut editor: StdioEditor,
+    tx_input: Sender<StdioInput>,
+    rx_output: Receiver<StdioOutput>,
+) {
     let mut prompt = "".to_owned();
+    // wait until the app has started and sent the first prompt
+    for output in rx_output.iter() {
+        let started = matches!(output, StdioOutput::Prompt(_));
+        apply_stdio_output(&mut editor, &mut prompt, output);
+        if started {
+            break;
+        }
+    }
+
     loop {
-        if let Some(new_prompt) = rx_prompt.try_iter().last() {
-            prompt = new_prompt;
+        for output in rx_output.try_iter() {
+            apply_stdio_output(&mut editor, &mut prompt, output);
         }
         match editor.readline(&prompt) {
             Ok(buf) => {
@@ -119,19 +262,34 @@ fn read_stdio(mut editor: StdioEditor, tx_input: Sender<StdioInput>, rx_prompt:
     }
 }
 
+fn apply_stdio_output(editor: &mut StdioEditor, prompt: &mut String, output: StdioOutput) {
+    match output {
+        StdioOutput::Prompt(new_prompt) => *prompt = new_prompt,
+        StdioOutput::History(entries) => {
+            if let Err(e) = editor.clear_history() {
+                warn!("Could not clear editor history: {}", e);
+            }
+            for entry in entries {
+                if let Err(e) = editor.add_history_entry(entry) {
+                    warn!("Could not add history entry to editor: {}", e);
+                }
+            }
+        }
+    }
+}
+
 fn receive_stdio_input(
     channels: NonSend<StdioChannels>,
     mut command_input: EventWriter<CommandBufInput>,
     mut app_exit: EventWriter<AppExit>,
     sender: Res<StdioCommandSender>,
+    mut history: ResMut<StdioHistory>,
 ) {
     for input in channels.rx_input.try_iter() {
         match input {
             StdioInput::Buf(buf) => {
-                command_input.send(CommandBufInput {
-                    sender: sender.0,
-                    buf,
-                });
+                history.push(buf.clone());
+                command_input.send(CommandBufInput::new(sender.0, buf));
             }
             StdioInput::Exit => app_exit.send(AppExit),
         }
@@ -142,11 +300,26 @@ fn send_stdio_prompt(channels: NonSend<StdioChannels>, prompt: Res<StdioPrompt>)
     if !prompt.is_changed() {
         return;
     }
-    if let Err(e) = channels.tx_prompt.send(prompt.0.clone()) {
+    if let Err(e) = channels
+        .tx_output
+        .send(StdioOutput::Prompt(prompt.0.clone()))
+    {
         warn!("Could not send new prompt to stdio: {}", e);
     }
 }
 
+fn send_stdio_history(channels: NonSend<StdioChannels>, history: Res<StdioHistory>) {
+    if history.entries.is_empty() {
+        return;
+    }
+    if let Err(e) = channels
+        .tx_output
+        .send(StdioOutput::History(history.entries.clone()))
+    {
+        warn!("Could not send history to stdio: {}", e);
+    }
+}
+
 fn respond_default_stdio(
     mut resps: EventReader<CommandResponse>,
     sender: Query<Entity, With<DefaultStdioCommandSender>>,
@@ -0,0 +1,562 @@
+use std::ops::Range;
+
+use clap::{error::ErrorKind, ArgAction};
+
+use crate::chain::{split_chain, validate_chain};
+use crate::completion::{find_flag, tokenize, CompletionData, Token};
+
+/// The role of a word in a command line, used for syntax highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    /// The name of a command or alias.
+    Command,
+    /// A first word which is not the name of a command, alias or cvar.
+    UnknownCommand,
+    /// The name of a cvar, used as a shorthand for `set` or `get`.
+    Cvar,
+    Subcommand,
+    /// A flag of the command, such as `-c` or `--count`.
+    Flag,
+    /// A word starting with `-` which is not a flag of the command.
+    UnknownFlag,
+    /// The value of a flag or cvar.
+    Value,
+    Positional,
+    /// A positional argument after all of the command's positional arguments.
+    UnexpectedPositional,
+    /// A chain operator: `;`, `&&` or `||`.
+    Operator,
+}
+
+/// Usage of the rest of a command, shown after the cursor while the command is being typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureHint {
+    /// Usage of the arguments which have not been typed yet, such as `<MESSAGE> [-c <COUNT>]`.
+    ///
+    /// This starts with a space if the cursor is at the end of a word.
+    pub usage: String,
+    /// Help text of the argument being typed.
+    pub help: Option<String>,
+}
+
+/// A range of bytes in a command line, and the role of the text in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxSpan {
+    pub range: Range<usize>,
+    pub kind: SyntaxKind,
+}
+
+impl CompletionData {
+    /// Classifies the words of a (possibly incomplete) command line, using the clap schema of
+    /// the commands in it.
+    ///
+    /// Each line of `buf` is treated as a separate command line. Text which is not covered by a
+    /// span, such as whitespace, has no role.
+    pub fn highlight(&self, buf: &str) -> Vec<SyntaxSpan> {
+        let mut spans = Vec::new();
+        let mut line_start = 0;
+        for line in buf.split('\n') {
+            for segment in split_chain(line) {
+                let start = line_start + segment.start;
+                if let Some(op) = segment.op {
+                    spans.push(SyntaxSpan {
+                        range: (start - op.as_str().len())..start,
+                        kind: SyntaxKind::Operator,
+                    });
+                }
+                self.highlight_command(&tokenize(segment.buf), start, &mut spans);
+            }
+            line_start += line.len() + 1;
+        }
+        spans
+    }
+
+    fn highlight_command(&self, tokens: &[Token], offset: usize, spans: &mut Vec<SyntaxSpan>) {
+        let mut push = |token: &Token, kind| {
+            spans.push(SyntaxSpan {
+                range: (offset + token.start)..(offset + token.end),
+                kind,
+            });
+        };
+        let Some((name, args)) = tokens.split_first() else {
+            return;
+        };
+
+        let Some(mut command) = self.find_command(&name.value) else {
+            let cvar = name.value.split('=').next().unwrap_or_default();
+            let (kind, arg_kind) = if self.is_user_alias(&name.value) {
+                (SyntaxKind::Command, SyntaxKind::Positional)
+            } else if self.find_cvar(cvar).is_some() {
+                (SyntaxKind::Cvar, SyntaxKind::Value)
+            } else {
+                (SyntaxKind::UnknownCommand, SyntaxKind::Positional)
+            };
+            push(name, kind);
+            for arg in args {
+                push(arg, arg_kind);
+            }
+            return;
+        };
+
+        push(name, SyntaxKind::Command);
+        let mut pending_value = false;
+        let mut positionals = 0;
+        let mut only_positionals = false;
+        for arg in args {
+            let kind = if std::mem::take(&mut pending_value) {
+                SyntaxKind::Value
+            } else if only_positionals {
+                positionals += 1;
+                positional_kind(command, positionals - 1)
+            } else if arg.value == "--" {
+                only_positionals = true;
+                SyntaxKind::Flag
+            } else if let Some(subcommand) = (positionals == 0)
+                .then(|| command.find_subcommand(&arg.value))
+                .flatten()
+            {
+                command = subcommand;
+                SyntaxKind::Subcommand
+            } else if let Some(flag) = find_flag(command, &arg.value) {
+                pending_value = flag.get_action().takes_values() && !arg.value.contains('=');
+                SyntaxKind::Flag
+            } else if arg.value.len() > 1 && arg.value.starts_with('-') {
+                SyntaxKind::UnknownFlag
+            } else {
+                positionals += 1;
+                positional_kind(command, positionals - 1)
+            };
+            push(arg, kind);
+        }
+    }
+
+    /// Creates a hint for the rest of the command being typed at the end of `buf`, if it invokes
+    /// a known command.
+    pub fn signature_hint(&self, buf: &str) -> Option<SignatureHint> {
+        let line = buf.rsplit('\n').next().unwrap_or_default();
+        let segment = split_chain(line).pop()?;
+        let mut tokens = tokenize(segment.buf);
+        let current = tokens
+            .last()
+            .is_some_and(|token| token.end == segment.buf.len())
+            .then(|| tokens.pop())
+            .flatten();
+
+        let Some((name, args)) = tokens.split_first() else {
+            // the command name is still being typed
+            let command = self.find_command(&current?.value)?;
+            return Some(SignatureHint {
+                usage: format!(" {}", Signature::new(command).usage()),
+                help: command.get_about().map(|s| s.to_string()),
+            });
+        };
+        let mut signature = Signature::new(self.find_command(&name.value)?);
+        for arg in args {
+            signature.push(&arg.value);
+        }
+
+        let help = match &current {
+            Some(current) => {
+                let arg = signature.current_arg(&current.value);
+                signature.push(&current.value);
+                arg
+            }
+            None => signature.pending.or_else(|| signature.next_positional()),
+        }
+        .and_then(arg_help);
+
+        let usage = signature.usage();
+        if usage.is_empty() && help.is_none() {
+            return None;
+        }
+        Some(SignatureHint {
+            usage: match (&current, usage.is_empty()) {
+                (Some(_), false) => format!(" {}", usage),
+                _ => usage,
+            },
+            help,
+        })
+    }
+
+    /// Checks that each line of `buf` would be accepted by the commands it invokes, returning
+    /// the first error otherwise.
+    ///
+    /// Commands invoked through user aliases and the cvar shorthand are not checked.
+    pub fn validate(&self, buf: &str) -> Result<(), String> {
+        for line in buf.lines() {
+            for segment in validate_chain(split_chain(line))? {
+                self.validate_command(segment.buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_command(&self, buf: &str) -> Result<(), String> {
+        let Some(mut args) = shlex::split(buf) else {
+            return Err(format!("Could not parse command: {}", buf.trim()));
+        };
+        if args.is_empty() {
+            return Ok(());
+        }
+        let name = args.remove(0);
+        let Some(command) = self.find_command(&name) else {
+            let cvar = name.split('=').next().unwrap_or_default();
+            if self.is_user_alias(&name) || self.find_cvar(cvar).is_some() {
+                return Ok(());
+            }
+            return Err(format!("No such command: {}", name));
+        };
+
+        match command.clone().try_get_matches_from(args) {
+            Ok(_) => Ok(()),
+            Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => {
+                Ok(())
+            }
+            Err(e) => Err(error_summary(&e)),
+        }
+    }
+}
+
+/// The arguments of a command which have been typed so far.
+struct Signature<'a> {
+    command: &'a clap::Command,
+    /// The flag whose value is expected next.
+    pending: Option<&'a clap::Arg>,
+    used_flags: Vec<&'a str>,
+    positionals: usize,
+    only_positionals: bool,
+}
+
+impl<'a> Signature<'a> {
+    const fn new(command: &'a clap::Command) -> Self {
+        Self {
+            command,
+            pending: None,
+            used_flags: Vec::new(),
+            positionals: 0,
+            only_positionals: false,
+        }
+    }
+
+    fn push(&mut self, arg: &str) {
+        if self.pending.take().is_some() {
+            return;
+        }
+        if self.only_positionals {
+            self.positionals += 1;
+        } else if arg == "--" {
+            self.only_positionals = true;
+        } else if let Some(subcommand) = (self.positionals == 0)
+            .then(|| self.command.find_subcommand(arg))
+            .flatten()
+        {
+            *self = Self::new(subcommand);
+        } else if let Some(flag) = find_flag(self.command, arg) {
+            self.used_flags.push(flag.get_id().as_str());
+            if flag.get_action().takes_values() && !arg.contains('=') {
+                self.pending = Some(flag);
+            }
+        } else {
+            self.positionals += 1;
+        }
+    }
+
+    /// Finds the argument which `arg` would be if it were pushed.
+    fn current_arg(&self, arg: &str) -> Option<&'a clap::Arg> {
+        if self.pending.is_some() {
+            return self.pending;
+        }
+        if !self.only_positionals && arg.starts_with('-') {
+            return find_flag(self.command, arg);
+        }
+        self.next_positional()
+    }
+
+    fn next_positional(&self) -> Option<&'a clap::Arg> {
+        self.command.get_positionals().nth(self.positionals)
+    }
+
+    /// Formats the arguments which have not been typed yet.
+    fn usage(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(flag) = self.pending {
+            parts.push(value_usage(flag));
+        }
+        if self.pending.is_none() && self.positionals == 0 && self.command.has_subcommands() {
+            parts.push(if self.command.is_subcommand_required_set() {
+                "<COMMAND>".to_owned()
+            } else {
+                "[COMMAND]".to_owned()
+            });
+        }
+        parts.extend(
+            self.command
+                .get_positionals()
+                .skip(self.positionals)
+                .filter(|arg| !arg.is_hide_set())
+                .map(positional_usage),
+        );
+        if !self.only_positionals {
+            parts.extend(
+                self.command
+                    .get_arguments()
+                    .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+                    .filter(|arg| {
+                        !matches!(
+                            arg.get_action(),
+                            ArgAction::Help
+                                | ArgAction::HelpShort
+                                | ArgAction::HelpLong
+                                | ArgAction::Version
+                        )
+                    })
+                    .filter(|arg| !self.used_flags.contains(&arg.get_id().as_str()))
+                    .map(|arg| {
+                        if arg.is_required_set() {
+                            flag_usage(arg)
+                        } else {
+                            format!("[{}]", flag_usage(arg))
+                        }
+                    }),
+            );
+        }
+        parts.join(" ")
+    }
+}
+
+/// Formats the name of a flag, followed by its value if it takes one, such as `-c <COUNT>`.
+fn flag_usage(arg: &clap::Arg) -> String {
+    let name = arg.get_short().map_or_else(
+        || {
+            format!(
+                "--{}",
+                arg.get_long().unwrap_or_else(|| arg.get_id().as_str())
+            )
+        },
+        |short| format!("-{}", short),
+    );
+    if arg.get_action().takes_values() {
+        format!("{} {}", name, value_usage(arg))
+    } else {
+        name
+    }
+}
+
+/// Formats the values of an argument, such as `<COUNT>` or `<FILES>...`.
+fn value_usage(arg: &clap::Arg) -> String {
+    let usage = arg.get_value_names().map_or_else(
+        || format!("<{}>", arg.get_id().as_str().to_uppercase()),
+        |names| {
+            names
+                .iter()
+                .map(|name| format!("<{}>", name))
+                .collect::<Vec<_>>()
+                .join(" ")
+        },
+    );
+    if arg
+        .get_num_args()
+        .is_some_and(|num_args| num_args.max_values() > 1)
+    {
+        format!("{}...", usage)
+    } else {
+        usage
+    }
+}
+
+/// Formats a positional argument, such as `<MESSAGE>`, or `[QUERY]` if it is optional.
+fn positional_usage(arg: &clap::Arg) -> String {
+    let usage = value_usage(arg);
+    if arg.is_required_set() {
+        usage
+    } else {
+        format!("[{}]", usage.replace(['<', '>'], ""))
+    }
+}
+
+/// Formats the help text of an argument, prefixed with its name.
+fn arg_help(arg: &clap::Arg) -> Option<String> {
+    let help = arg.get_help()?;
+    let name = if arg.is_positional() {
+        value_usage(arg)
+    } else {
+        flag_usage(arg)
+    };
+    Some(format!("{}: {}", name, help))
+}
+
+fn positional_kind(command: &clap::Command, index: usize) -> SyntaxKind {
+    let accepted = command.get_positionals().nth(index).is_some()
+        || command
+            .get_positionals()
+            .last()
+            .and_then(|arg| arg.get_num_args())
+            .is_some_and(|num_args| num_args.max_values() > 1);
+    if accepted {
+        SyntaxKind::Positional
+    } else {
+        SyntaxKind::UnexpectedPositional
+    }
+}
+
+/// Joins the lines of a rendered clap error before its usage section into one line, without
+/// the `error: ` prefix.
+fn error_summary(e: &clap::Error) -> String {
+    let rendered = e.render().to_string();
+    let summary = rendered
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ");
+    summary
+        .strip_prefix("error: ")
+        .unwrap_or(&summary)
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::completion::tests::data;
+
+    fn highlight(buf: &str) -> Vec<(&str, SyntaxKind)> {
+        data()
+            .highlight(buf)
+            .into_iter()
+            .map(|span| (&buf[span.range], span.kind))
+            .collect()
+    }
+
+    #[test]
+    fn highlight_command() {
+        use SyntaxKind::*;
+        assert_eq!(
+            highlight("echo hi"),
+            [("echo", Command), ("hi", Positional)]
+        );
+        assert_eq!(
+            highlight("say 'a b' c"),
+            [
+                ("say", Command),
+                ("'a b'", Positional),
+                ("c", UnexpectedPositional)
+            ]
+        );
+        assert_eq!(
+            highlight("log -l warn --bogus x"),
+            [
+                ("log", Command),
+                ("-l", Flag),
+                ("warn", Value),
+                ("--bogus", UnknownFlag),
+                ("x", UnexpectedPositional)
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_aliases_cvars_and_unknown_commands() {
+        use SyntaxKind::*;
+        assert_eq!(
+            highlight("hi there"),
+            [("hi", Command), ("there", Positional)]
+        );
+        assert_eq!(highlight("gravity 1"), [("gravity", Cvar), ("1", Value)]);
+        assert_eq!(highlight("gravity=1"), [("gravity=1", Cvar)]);
+        assert_eq!(
+            highlight("nope x"),
+            [("nope", UnknownCommand), ("x", Positional)]
+        );
+    }
+
+    #[test]
+    fn highlight_chains_and_lines() {
+        use SyntaxKind::*;
+        assert_eq!(
+            highlight("echo a && nope;log"),
+            [
+                ("echo", Command),
+                ("a", Positional),
+                ("&&", Operator),
+                ("nope", UnknownCommand),
+                (";", Operator),
+                ("log", Command)
+            ]
+        );
+        let spans = data().highlight("echo a\nlog");
+        assert_eq!(spans.last().unwrap().range, 7..10);
+    }
+
+    #[test]
+    fn validate() {
+        let data = data();
+        assert_eq!(data.validate("echo hi; log --verbose"), Ok(()));
+        assert_eq!(data.validate("hi; gravity 1; echo --help"), Ok(()));
+        assert_eq!(data.validate(""), Ok(()));
+        assert_eq!(
+            data.validate("echo a\nnope"),
+            Err("No such command: nope".to_owned())
+        );
+        assert_eq!(
+            data.validate("echo a && && echo b"),
+            Err("Expected a command around '&&'".to_owned())
+        );
+        assert!(data.validate("echo").unwrap_err().contains("<message>"));
+        assert!(data.validate("log -l bad").unwrap_err().contains("bad"));
+        assert!(data.validate("echo \"a").is_err());
+    }
+
+    fn hint(buf: &str) -> Option<(String, Option<String>)> {
+        data()
+            .signature_hint(buf)
+            .map(|hint| (hint.usage, hint.help))
+    }
+
+    #[test]
+    fn hint_command_name() {
+        assert_eq!(
+            hint("echo"),
+            Some((" <MESSAGE>".to_owned(), Some("Prints a message".to_owned())))
+        );
+        assert_eq!(hint("ech"), None);
+        assert_eq!(hint("nope "), None);
+    }
+
+    #[test]
+    fn hint_positionals() {
+        let help = Some("<MESSAGE>: The message to print".to_owned());
+        assert_eq!(hint("echo "), Some(("<MESSAGE>".to_owned(), help.clone())));
+        // the argument being typed is still described
+        assert_eq!(hint("say hi"), Some((String::new(), help)));
+        assert_eq!(hint("say hi "), None);
+    }
+
+    #[test]
+    fn hint_flags() {
+        assert_eq!(
+            hint("log "),
+            Some(("[-l <LEVEL>] [--verbose]".to_owned(), None))
+        );
+        assert_eq!(
+            hint("log -l "),
+            Some((
+                "<LEVEL> [--verbose]".to_owned(),
+                Some("-l <LEVEL>: The level to log at".to_owned())
+            ))
+        );
+        assert_eq!(
+            hint("log --verbose "),
+            Some(("[-l <LEVEL>]".to_owned(), None))
+        );
+    }
+
+    #[test]
+    fn hint_last_command() {
+        assert_eq!(hint("log --verbose; echo "), hint("echo "));
+        assert_eq!(
+            hint("echo a\nlog -l warn "),
+            Some(("[--verbose]".to_owned(), None))
+        );
+    }
+}
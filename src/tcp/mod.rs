@@ -0,0 +1,347 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::dispatch::complete_commands;
+use crate::{CommandBufInput, CommandPermissions, CommandResponse, CommandSet};
+
+pub mod rcon;
+
+use self::rcon::{read_rcon, respond_rcon, RconRequest, RconRequests};
+
+/// Delay before replying to a client which sent the wrong password, to slow down guessing.
+const AUTH_FAILURE_DELAY: Duration = Duration::from_secs(1);
+
+/// Maximum length of the password line, so unauthenticated clients can't fill up memory.
+const MAX_PASSWORD_LINE: u64 = 1024;
+
+/// Accepts commands from remote console (RCON) clients over TCP.
+///
+/// Clients must authenticate with the password before sending commands, using the given
+/// [`TcpProtocol`]. Each authenticated client is its own command sender, with a [`TcpClient`]
+/// component.
+pub struct TcpInputPlugin {
+    /// Address to listen on, such as `0.0.0.0:27015`. If the port is 0, a free port is picked,
+    /// which can be read from [`TcpInputAddr`].
+    pub address: String,
+    /// The password clients authenticate with. If it is empty, the plugin doesn't listen.
+    pub password: String,
+    pub protocol: TcpProtocol,
+    /// Permissions granted to each authenticated client. By default no nodes are granted, so
+    /// clients may only run commands which don't require a permission. If this is `None`,
+    /// clients may run any command.
+    pub permissions: Option<CommandPermissions>,
+}
+
+impl TcpInputPlugin {
+    pub fn new(address: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            password: password.into(),
+            protocol: TcpProtocol::Lines,
+            permissions: Some(CommandPermissions::new()),
+        }
+    }
+
+    pub const fn with_protocol(mut self, protocol: TcpProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn with_permissions(mut self, permissions: Option<CommandPermissions>) -> Self {
+        self.permissions = permissions;
+        self
+    }
+}
+
+/// The protocol which clients of [`TcpInputPlugin`] use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TcpProtocol {
+    /// A line-based protocol, which can be used with tools like `netcat`.
+    ///
+    /// The first line a client sends must be the password, which is answered with
+    /// `Authenticated` or `Authentication failed`. After that, every line is sent as a command,
+    /// and responses to the client's commands are sent back one message per line.
+    #[default]
+    Lines,
+    /// The [Source RCON protocol](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol),
+    /// which is supported by existing RCON tools.
+    ///
+    /// All responses to a `SERVERDATA_EXECCOMMAND` packet are sent once the command completes,
+    /// in `SERVERDATA_RESPONSE_VALUE` packets with the same ID.
+    SourceRcon,
+}
+
+impl Plugin for TcpInputPlugin {
+    fn build(&self, app: &mut App) {
+        if self.password.is_empty() {
+            error!(
+                "Not listening for TCP clients on {}: the password is empty",
+                self.address
+            );
+            return;
+        }
+        let listener = match TcpListener::bind(&self.address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!(
+                    "Could not listen for TCP clients on {}: {}",
+                    self.address, e
+                );
+                return;
+            }
+        };
+        let addr = match listener.local_addr() {
+            Ok(addr) => addr,
+            Err(e) => {
+                error!("Could not get address of TCP listener: {}", e);
+                return;
+            }
+        };
+        info!("Listening for TCP clients on {}", addr);
+
+        let (tx_event, rx_event) = mpsc::channel::<TcpEvent>();
+        let password = self.password.clone();
+        let protocol = self.protocol;
+        thread::spawn(move || accept_tcp(listener, password, protocol, tx_event));
+
+        app.insert_resource(TcpInputAddr(addr))
+            .insert_resource(TcpClientPermissions(self.permissions.clone()))
+            .init_resource::<TcpClients>()
+            .init_resource::<RconRequests>()
+            .insert_non_send_resource(TcpChannels { rx_event })
+            .add_systems(Update, receive_tcp_events.in_set(CommandSet::Dispatch))
+            .add_systems(Update, respond_tcp.in_set(CommandSet::Response))
+            // responses are collected before the chains they are part of are advanced
+            .add_systems(PostUpdate, respond_rcon.before(complete_commands));
+    }
+}
+
+/// The address which [`TcpInputPlugin`] is listening on.
+#[derive(Resource)]
+pub struct TcpInputAddr(pub SocketAddr);
+
+/// A client connected through [`TcpInputPlugin`]. Responses sent to this entity are sent to
+/// the client.
+#[derive(Component)]
+pub struct TcpClient {
+    pub peer: SocketAddr,
+    pub protocol: TcpProtocol,
+    tx_output: Sender<Vec<u8>>,
+}
+
+#[derive(Resource)]
+struct TcpClientPermissions(Option<CommandPermissions>);
+
+/// Sender entities of the connected clients, keyed by connection ID.
+#[derive(Resource, Default)]
+struct TcpClients(HashMap<u64, Entity>);
+
+struct TcpChannels {
+    rx_event: Receiver<TcpEvent>,
+}
+
+enum TcpEvent {
+    Connected {
+        id: u64,
+        peer: SocketAddr,
+        protocol: TcpProtocol,
+        tx_output: Sender<Vec<u8>>,
+    },
+    Command {
+        id: u64,
+        buf: String,
+        /// ID of the RCON packet which the command was sent in.
+        request_id: Option<i32>,
+    },
+    /// An empty RCON response value, which is mirrored back after the responses to the
+    /// commands sent before it.
+    RconMirror {
+        id: u64,
+        request_id: i32,
+    },
+    Disconnected {
+        id: u64,
+    },
+}
+
+fn accept_tcp(
+    listener: TcpListener,
+    password: String,
+    protocol: TcpProtocol,
+    tx_event: Sender<TcpEvent>,
+) {
+    for (id, stream) in (0..).zip(listener.incoming()) {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Could not accept TCP client: {}", e);
+                continue;
+            }
+        };
+        let password = password.clone();
+        let tx_event = tx_event.clone();
+        thread::spawn(move || {
+            let result = match protocol {
+                TcpProtocol::Lines => read_tcp(id, stream, &password, &tx_event),
+                TcpProtocol::SourceRcon => read_rcon(id, stream, &password, &tx_event),
+            };
+            if let Err(e) = result {
+                debug!("TCP client {} disconnected: {}", id, e);
+            }
+            let _ = tx_event.send(TcpEvent::Disconnected { id });
+        });
+    }
+}
+
+fn read_tcp(
+    id: u64,
+    stream: TcpStream,
+    password: &str,
+    tx_event: &Sender<TcpEvent>,
+) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let mut line = String::new();
+    let len = (&mut reader).take(MAX_PASSWORD_LINE).read_line(&mut line)?;
+    if len as u64 == MAX_PASSWORD_LINE && !line.ends_with('\n') {
+        warn!("TCP client {} sent a password line which is too long", peer);
+        return Ok(());
+    }
+    if line.trim_end_matches(['\r', '\n']) != password {
+        warn!("TCP client {} sent the wrong password", peer);
+        thread::sleep(AUTH_FAILURE_DELAY);
+        writeln!(writer, "Authentication failed")?;
+        return Ok(());
+    }
+    writeln!(writer, "Authenticated")?;
+
+    if tx_event
+        .send(TcpEvent::Connected {
+            id,
+            peer,
+            protocol: TcpProtocol::Lines,
+            tx_output: spawn_writer(writer),
+        })
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    for line in reader.lines() {
+        let line = line?;
+        let buf = line.strip_suffix('\r').unwrap_or(&line).to_owned();
+        let event = TcpEvent::Command {
+            id,
+            buf,
+            request_id: None,
+        };
+        if tx_event.send(event).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the output sent to the returned sender to the stream, until the sender is dropped
+/// along with the client's entity.
+fn spawn_writer(mut writer: TcpStream) -> Sender<Vec<u8>> {
+    let (tx_output, rx_output) = mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        for output in rx_output {
+            if writer.write_all(&output).is_err() {
+                break;
+            }
+        }
+    });
+    tx_output
+}
+
+fn receive_tcp_events(
+    mut commands: Commands,
+    channels: NonSend<TcpChannels>,
+    mut clients: ResMut<TcpClients>,
+    permissions: Res<TcpClientPermissions>,
+    mut rcon_requests: ResMut<RconRequests>,
+    mut command_input: EventWriter<CommandBufInput>,
+) {
+    for event in channels.rx_event.try_iter() {
+        match event {
+            TcpEvent::Connected {
+                id,
+                peer,
+                protocol,
+                tx_output,
+            } => {
+                info!("TCP client {} connected", peer);
+                let mut client = commands.spawn((
+                    Name::new(format!("TCP client {}", peer)),
+                    TcpClient {
+                        peer,
+                        protocol,
+                        tx_output,
+                    },
+                ));
+                if let Some(permissions) = &permissions.0 {
+                    client.insert(permissions.clone());
+                }
+                clients.0.insert(id, client.id());
+            }
+            TcpEvent::Command {
+                id,
+                buf,
+                request_id,
+            } => {
+                let Some(&sender) = clients.0.get(&id) else {
+                    continue;
+                };
+                let input = CommandBufInput::new(sender, buf);
+                if let Some(request_id) = request_id {
+                    rcon_requests
+                        .0
+                        .entry(sender)
+                        .or_default()
+                        .push_back(RconRequest::command(request_id, input.id));
+                }
+                command_input.send(input);
+            }
+            TcpEvent::RconMirror { id, request_id } => {
+                if let Some(&sender) = clients.0.get(&id) {
+                    rcon_requests
+                        .0
+                        .entry(sender)
+                        .or_default()
+                        .push_back(RconRequest::Mirror { request_id });
+                }
+            }
+            TcpEvent::Disconnected { id } => {
+                if let Some(sender) = clients.0.remove(&id) {
+                    info!("TCP client {:?} disconnected", sender);
+                    rcon_requests.0.remove(&sender);
+                    commands.entity(sender).despawn();
+                }
+            }
+        }
+    }
+}
+
+fn respond_tcp(mut resps: EventReader<CommandResponse>, clients: Query<&TcpClient>) {
+    for resp in resps.iter() {
+        let Ok(client) = clients.get(resp.target) else {
+            continue;
+        };
+        if client.protocol != TcpProtocol::Lines {
+            continue;
+        }
+        // the writer may already have stopped if the client disconnected
+        let _ = client
+            .tx_output
+            .send(format!("{}\n", resp.message).into_bytes());
+    }
+}
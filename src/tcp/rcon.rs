@@ -0,0 +1,340 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{spawn_writer, TcpClient, TcpEvent, TcpProtocol, AUTH_FAILURE_DELAY};
+use crate::chain::CommandChains;
+use crate::{CommandCompleted, CommandId, CommandResponse};
+
+pub const SERVERDATA_AUTH: i32 = 3;
+pub const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+pub const SERVERDATA_EXECCOMMAND: i32 = 2;
+pub const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// Largest value of the size field of a packet.
+pub const MAX_PACKET_SIZE: usize = 4096;
+/// Size of the ID, type and the two null terminators, which are counted in the size field.
+const PACKET_OVERHEAD: usize = 10;
+/// Largest body which fits in a single packet.
+pub const MAX_BODY_SIZE: usize = MAX_PACKET_SIZE - PACKET_OVERHEAD;
+
+/// A packet of the Source RCON protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconPacket {
+    pub id: i32,
+    pub ty: i32,
+    pub body: String,
+}
+
+impl RconPacket {
+    pub fn new(id: i32, ty: i32, body: impl Into<String>) -> Self {
+        Self {
+            id,
+            ty,
+            body: body.into(),
+        }
+    }
+
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut size = [0; 4];
+        reader.read_exact(&mut size)?;
+        let size = i32::from_le_bytes(size);
+        let size = usize::try_from(size)
+            .ok()
+            .filter(|size| (PACKET_OVERHEAD..=MAX_PACKET_SIZE).contains(size))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid packet size {}", size),
+                )
+            })?;
+
+        let mut data = vec![0; size];
+        reader.read_exact(&mut data)?;
+        let id = i32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let ty = i32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let body = &data[8..];
+        let body = &body[..body.iter().position(|b| *b == 0).unwrap_or(body.len())];
+        Ok(Self {
+            id,
+            ty,
+            body: String::from_utf8_lossy(body).into_owned(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = self.body.len() + PACKET_OVERHEAD;
+        let mut bytes = Vec::with_capacity(size + 4);
+        bytes.extend((size as i32).to_le_bytes());
+        bytes.extend(self.id.to_le_bytes());
+        bytes.extend(self.ty.to_le_bytes());
+        bytes.extend(self.body.as_bytes());
+        bytes.extend([0, 0]);
+        bytes
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+/// Splits a response into `SERVERDATA_RESPONSE_VALUE` packets whose bodies are at most
+/// [`MAX_BODY_SIZE`] bytes long, without splitting characters.
+pub fn response_packets(id: i32, mut body: &str) -> Vec<RconPacket> {
+    let mut packets = Vec::new();
+    while body.len() > MAX_BODY_SIZE {
+        let mut end = MAX_BODY_SIZE;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        packets.push(RconPacket::new(id, SERVERDATA_RESPONSE_VALUE, &body[..end]));
+        body = &body[end..];
+    }
+    packets.push(RconPacket::new(id, SERVERDATA_RESPONSE_VALUE, body));
+    packets
+}
+
+pub(super) fn read_rcon(
+    id: u64,
+    stream: TcpStream,
+    password: &str,
+    tx_event: &Sender<TcpEvent>,
+) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let auth = RconPacket::read(&mut reader)?;
+    if auth.ty != SERVERDATA_AUTH || auth.body != password {
+        warn!("RCON client {} sent the wrong password", peer);
+        thread::sleep(AUTH_FAILURE_DELAY);
+        RconPacket::new(-1, SERVERDATA_AUTH_RESPONSE, "").write(&mut writer)?;
+        return Ok(());
+    }
+    // clients expect an empty response value before the auth response
+    RconPacket::new(auth.id, SERVERDATA_RESPONSE_VALUE, "").write(&mut writer)?;
+    RconPacket::new(auth.id, SERVERDATA_AUTH_RESPONSE, "").write(&mut writer)?;
+
+    if tx_event
+        .send(TcpEvent::Connected {
+            id,
+            peer,
+            protocol: TcpProtocol::SourceRcon,
+            tx_output: spawn_writer(writer),
+        })
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    loop {
+        let packet = match RconPacket::read(&mut reader) {
+            Ok(packet) => packet,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let event = match packet.ty {
+            SERVERDATA_EXECCOMMAND => TcpEvent::Command {
+                id,
+                buf: packet.body,
+                request_id: Some(packet.id),
+            },
+            SERVERDATA_RESPONSE_VALUE => TcpEvent::RconMirror {
+                id,
+                request_id: packet.id,
+            },
+            ty => {
+                debug!("Ignoring RCON packet of type {} from {}", ty, peer);
+                continue;
+            }
+        };
+        if tx_event.send(event).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// A request from an RCON client which has not been answered yet.
+pub(super) enum RconRequest {
+    Command {
+        request_id: i32,
+        command_id: CommandId,
+        body: String,
+        done: bool,
+    },
+    /// An empty response value, which clients send after a command and wait for to be sent
+    /// back, to know when all responses to the command have been received.
+    Mirror { request_id: i32 },
+}
+
+impl RconRequest {
+    pub const fn command(request_id: i32, command_id: CommandId) -> Self {
+        Self::Command {
+            request_id,
+            command_id,
+            body: String::new(),
+            done: false,
+        }
+    }
+
+    const fn command_id(&self) -> Option<CommandId> {
+        match self {
+            Self::Command { command_id, .. } => Some(*command_id),
+            Self::Mirror { .. } => None,
+        }
+    }
+
+    const fn is_done(&self) -> bool {
+        match self {
+            Self::Command { done, .. } => *done,
+            Self::Mirror { .. } => true,
+        }
+    }
+
+    fn packets(&self) -> Vec<RconPacket> {
+        match self {
+            Self::Command {
+                request_id, body, ..
+            } => response_packets(*request_id, body),
+            Self::Mirror { request_id } => response_packets(*request_id, ""),
+        }
+    }
+}
+
+/// Requests of each RCON client, oldest first. Requests are answered in order.
+#[derive(Resource, Default)]
+pub(super) struct RconRequests(pub HashMap<Entity, VecDeque<RconRequest>>);
+
+pub(super) fn respond_rcon(
+    mut resps: EventReader<CommandResponse>,
+    mut completed: EventReader<CommandCompleted>,
+    chains: Res<CommandChains>,
+    mut requests: ResMut<RconRequests>,
+    clients: Query<&TcpClient>,
+) {
+    requests.0.retain(|sender, _| clients.contains(*sender));
+
+    for resp in resps.iter() {
+        let Some(pending) = requests.0.get_mut(&resp.target) else {
+            continue;
+        };
+        let id = chains.root_id(resp.id);
+        // responses to commands which the client did not send directly, such as the commands in
+        // a script, are added to the oldest command
+        let index = pending
+            .iter()
+            .position(|request| request.command_id() == Some(id))
+            .or_else(|| {
+                pending
+                    .iter()
+                    .position(|request| request.command_id().is_some() && !request.is_done())
+            });
+        let Some(RconRequest::Command { body, .. }) = index.and_then(|i| pending.get_mut(i)) else {
+            debug!(
+                "Dropping response to {:?} with no RCON request: {}",
+                resp.target, resp.message
+            );
+            continue;
+        };
+        if !body.is_empty() {
+            body.push('\n');
+        }
+        body.push_str(&resp.message.to_string());
+    }
+
+    for event in completed.iter() {
+        let Some(pending) = requests.0.get_mut(&event.target) else {
+            continue;
+        };
+        for request in pending.iter_mut() {
+            if let RconRequest::Command {
+                command_id, done, ..
+            } = request
+            {
+                *done |= *command_id == event.id;
+            }
+        }
+    }
+
+    for (sender, pending) in &mut requests.0 {
+        let Ok(client) = clients.get(*sender) else {
+            continue;
+        };
+        while pending.front().is_some_and(RconRequest::is_done) {
+            let Some(request) = pending.pop_front() else {
+                break;
+            };
+            for packet in request.packets() {
+                // the writer may already have stopped if the client disconnected
+                let _ = client.tx_output.send(packet.to_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_round_trip() {
+        let packet = RconPacket::new(7, SERVERDATA_EXECCOMMAND, "echo hi");
+        let bytes = packet.to_bytes();
+        assert_eq!(bytes.len(), 4 + PACKET_OVERHEAD + packet.body.len());
+        assert_eq!(RconPacket::read(&mut bytes.as_slice()).unwrap(), packet);
+
+        let empty = RconPacket::new(-1, SERVERDATA_AUTH_RESPONSE, "");
+        assert_eq!(
+            RconPacket::read(&mut empty.to_bytes().as_slice()).unwrap(),
+            empty
+        );
+    }
+
+    #[test]
+    fn packet_size_limits() {
+        let largest = RconPacket::new(1, SERVERDATA_RESPONSE_VALUE, "a".repeat(MAX_BODY_SIZE));
+        assert_eq!(
+            RconPacket::read(&mut largest.to_bytes().as_slice()).unwrap(),
+            largest
+        );
+
+        let too_large =
+            RconPacket::new(1, SERVERDATA_RESPONSE_VALUE, "a".repeat(MAX_BODY_SIZE + 1));
+        let err = RconPacket::read(&mut too_large.to_bytes().as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        for size in [-1, 0, PACKET_OVERHEAD as i32 - 1] {
+            let mut bytes = size.to_le_bytes().to_vec();
+            bytes.extend([0; PACKET_OVERHEAD]);
+            let err = RconPacket::read(&mut bytes.as_slice()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn response_packets_split_on_char_boundaries() {
+        assert_eq!(
+            response_packets(3, ""),
+            [RconPacket::new(3, SERVERDATA_RESPONSE_VALUE, "")]
+        );
+
+        // the 3-byte character straddles the body limit, so it starts the second packet
+        let body = format!("{}€{}", "a".repeat(MAX_BODY_SIZE - 1), "b".repeat(5));
+        let packets = response_packets(3, &body);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].body, "a".repeat(MAX_BODY_SIZE - 1));
+        assert_eq!(packets[1].body, format!("€{}", "b".repeat(5)));
+        assert!(packets.iter().all(|packet| packet.id == 3
+            && packet.ty == SERVERDATA_RESPONSE_VALUE
+            && packet.body.len() <= MAX_BODY_SIZE));
+        let joined = packets
+            .iter()
+            .map(|packet| packet.body.as_str())
+            .collect::<String>();
+        assert_eq!(joined, body);
+    }
+}
@@ -0,0 +1,471 @@
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+use expedition::{Message, StackFlattener};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::{header, StatusCode};
+use tungstenite::{Message as WsMessage, WebSocket};
+
+use crate::chain::CommandChains;
+use crate::dispatch::complete_commands;
+use crate::{
+    CommandBufInput, CommandCompleted, CommandId, CommandPermissions, CommandResponse, CommandSet,
+    Outcome,
+};
+
+/// How long a socket waits for a request before sending the replies queued for it.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Delay before rejecting a client which failed to authenticate, to slow down guessing.
+const AUTH_FAILURE_DELAY: Duration = Duration::from_secs(1);
+
+/// Accepts commands as JSON messages over WebSockets.
+///
+/// Clients send [`WebSocketRequest`]s, and are sent a [`WebSocketReply::Response`] for every
+/// response to the command, followed by a [`WebSocketReply::Completed`] once it completes.
+///
+/// Clients must authenticate with the token when connecting, and browsers may only connect
+/// from the allowed origins. Each client is its own command sender, with a [`WebSocketClient`]
+/// component.
+pub struct WebSocketInputPlugin {
+    /// Address to listen on, such as `127.0.0.1:8080`. If the port is 0, a free port is picked,
+    /// which can be read from [`WebSocketInputAddr`].
+    pub address: String,
+    /// The token clients authenticate with, sent as the `token` query parameter of the URL they
+    /// connect to, such as `ws://127.0.0.1:8080/?token=secret`. It is compared without decoding,
+    /// so it should only contain characters which are allowed in URLs. If it is empty, the plugin
+    /// doesn't listen.
+    pub token: String,
+    /// Origins which browsers may connect from, such as `https://example.com`. Connections with
+    /// any other `Origin` header are rejected, so other web pages can't connect through the
+    /// browser of someone who has the token. Clients which don't send an `Origin` are allowed.
+    pub allowed_origins: Vec<String>,
+    /// Permissions granted to each client. By default no nodes are granted, so clients may only
+    /// run commands which don't require a permission. If this is `None`, clients may run any
+    /// command.
+    pub permissions: Option<CommandPermissions>,
+}
+
+impl WebSocketInputPlugin {
+    pub fn new(address: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            token: token.into(),
+            allowed_origins: Vec::new(),
+            permissions: Some(CommandPermissions::new()),
+        }
+    }
+
+    pub fn with_allowed_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allowed_origins.push(origin.into());
+        self
+    }
+
+    pub fn with_permissions(mut self, permissions: Option<CommandPermissions>) -> Self {
+        self.permissions = permissions;
+        self
+    }
+}
+
+impl Plugin for WebSocketInputPlugin {
+    fn build(&self, app: &mut App) {
+        if self.token.is_empty() {
+            error!(
+                "Not listening for WebSocket clients on {}: the token is empty",
+                self.address
+            );
+            return;
+        }
+        let listener = match TcpListener::bind(&self.address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!(
+                    "Could not listen for WebSocket clients on {}: {}",
+                    self.address, e
+                );
+                return;
+            }
+        };
+        let addr = match listener.local_addr() {
+            Ok(addr) => addr,
+            Err(e) => {
+                error!("Could not get address of WebSocket listener: {}", e);
+                return;
+            }
+        };
+        info!("Listening for WebSocket clients on {}", addr);
+
+        let (tx_event, rx_event) = mpsc::channel::<WebSocketEvent>();
+        let auth = WebSocketAuth {
+            token: self.token.clone(),
+            allowed_origins: self.allowed_origins.clone(),
+        };
+        thread::spawn(move || accept_websockets(listener, auth, tx_event));
+
+        app.insert_resource(WebSocketInputAddr(addr))
+            .insert_resource(WebSocketClientPermissions(self.permissions.clone()))
+            .init_resource::<WebSocketClients>()
+            .init_resource::<WebSocketRequests>()
+            .insert_non_send_resource(WebSocketChannels { rx_event })
+            .add_systems(
+                Update,
+                receive_websocket_events.in_set(CommandSet::Dispatch),
+            )
+            // responses are collected before the chains they are part of are advanced
+            .add_systems(PostUpdate, respond_websockets.before(complete_commands));
+    }
+}
+
+/// The address which [`WebSocketInputPlugin`] is listening on.
+#[derive(Resource)]
+pub struct WebSocketInputAddr(pub SocketAddr);
+
+/// A client connected through [`WebSocketInputPlugin`]. Responses sent to this entity are sent
+/// to the client.
+#[derive(Component)]
+pub struct WebSocketClient {
+    pub peer: SocketAddr,
+    tx_output: Sender<WebSocketReply>,
+}
+
+/// A command line sent by a client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSocketRequest {
+    /// Chosen by the client, and included in the replies to this request.
+    #[serde(default)]
+    pub id: Value,
+    pub line: String,
+}
+
+/// A message sent to a client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebSocketReply {
+    /// A response to a command.
+    ///
+    /// `id` is null if the command was not sent in a request, such as a command run by a
+    /// script.
+    Response {
+        id: Value,
+        /// ID of the invocation of the command line sent in the request.
+        invocation: u64,
+        outcome: Outcome,
+        /// The message without styling.
+        message: String,
+        /// The parts of the message, with their styles.
+        spans: Vec<WebSocketSpan>,
+    },
+    /// The command line sent in a request has completed, and there will be no more responses
+    /// to it.
+    Completed {
+        id: Value,
+        invocation: u64,
+        outcome: Outcome,
+    },
+    /// A request could not be parsed.
+    Invalid { error: String },
+}
+
+/// A part of a message with a single style.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebSocketSpan {
+    pub text: String,
+    /// An unmultiplied sRGBA color, formatted as `#rrggbbaa`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underline: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+}
+
+impl WebSocketSpan {
+    /// Splits a message into spans, one for each part of it with content.
+    pub fn from_message(message: &Message) -> Vec<Self> {
+        let mut spans = Vec::new();
+        let mut flattener = StackFlattener::new(|text: &str, style| {
+            if text.is_empty() {
+                return;
+            }
+            spans.push(Self {
+                text: text.to_owned(),
+                color: style.color.map(|color| {
+                    let [r, g, b, a] = color.to_srgba_unmultiplied();
+                    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+                }),
+                bold: style.bold,
+                italic: style.italic,
+                underline: style.underline,
+                strikethrough: style.strikethrough,
+            });
+        });
+        message.flatten(&mut flattener);
+        spans
+    }
+}
+
+#[derive(Resource)]
+struct WebSocketClientPermissions(Option<CommandPermissions>);
+
+/// Sender entities of the connected clients, keyed by connection ID.
+#[derive(Resource, Default)]
+struct WebSocketClients(HashMap<u64, Entity>);
+
+/// IDs of the requests of each client which have not completed, keyed by invocation.
+#[derive(Resource, Default)]
+struct WebSocketRequests(HashMap<Entity, HashMap<CommandId, Value>>);
+
+struct WebSocketChannels {
+    rx_event: Receiver<WebSocketEvent>,
+}
+
+enum WebSocketEvent {
+    Connected {
+        id: u64,
+        peer: SocketAddr,
+        tx_output: Sender<WebSocketReply>,
+    },
+    Request {
+        id: u64,
+        request: WebSocketRequest,
+    },
+    Disconnected {
+        id: u64,
+    },
+}
+
+/// What a client's handshake request is checked against.
+#[derive(Clone)]
+struct WebSocketAuth {
+    token: String,
+    allowed_origins: Vec<String>,
+}
+
+impl WebSocketAuth {
+    /// Checks the handshake request of a client, returning the reason it is rejected if it is.
+    fn check(&self, request: &Request) -> Result<(), (StatusCode, &'static str)> {
+        if let Some(origin) = request.headers().get(header::ORIGIN) {
+            let allowed = origin
+                .to_str()
+                .is_ok_and(|origin| self.allowed_origins.iter().any(|o| o == origin));
+            if !allowed {
+                return Err((StatusCode::FORBIDDEN, "Origin not allowed"));
+            }
+        }
+        let token = request
+            .uri()
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .find_map(|param| param.strip_prefix("token="));
+        if token != Some(self.token.as_str()) {
+            return Err((StatusCode::UNAUTHORIZED, "Authentication failed"));
+        }
+        Ok(())
+    }
+}
+
+fn accept_websockets(listener: TcpListener, auth: WebSocketAuth, tx_event: Sender<WebSocketEvent>) {
+    for (id, stream) in (0..).zip(listener.incoming()) {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Could not accept WebSocket client: {}", e);
+                continue;
+            }
+        };
+        let auth = auth.clone();
+        let tx_event = tx_event.clone();
+        thread::spawn(move || {
+            if let Err(e) = run_websocket(id, stream, &auth, &tx_event) {
+                debug!("WebSocket client {} disconnected: {}", id, e);
+            }
+            let _ = tx_event.send(WebSocketEvent::Disconnected { id });
+        });
+    }
+}
+
+fn run_websocket(
+    id: u64,
+    stream: TcpStream,
+    auth: &WebSocketAuth,
+    tx_event: &Sender<WebSocketEvent>,
+) -> Result<(), String> {
+    let peer = stream.peer_addr().map_err(|e| e.to_string())?;
+    // the error response type is set by tungstenite
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, response: Response| match auth.check(request) {
+        Ok(()) => Ok(response),
+        Err((status, reason)) => {
+            warn!("WebSocket client {} was rejected: {}", peer, reason);
+            thread::sleep(AUTH_FAILURE_DELAY);
+            let mut error = ErrorResponse::new(Some(reason.to_owned()));
+            *error.status_mut() = status;
+            Err(error)
+        }
+    };
+    let mut socket = tungstenite::accept_hdr(stream, callback).map_err(|e| e.to_string())?;
+    // reads time out, so that replies can be sent while waiting for requests
+    socket
+        .get_ref()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(|e| e.to_string())?;
+
+    let (tx_output, rx_output) = mpsc::channel::<WebSocketReply>();
+    if tx_event
+        .send(WebSocketEvent::Connected {
+            id,
+            peer,
+            tx_output,
+        })
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    loop {
+        match socket.read() {
+            Ok(WsMessage::Text(text)) => match serde_json::from_str(&text) {
+                Ok(request) => {
+                    if tx_event
+                        .send(WebSocketEvent::Request { id, request })
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+                Err(e) => send_reply(
+                    &mut socket,
+                    &WebSocketReply::Invalid {
+                        error: e.to_string(),
+                    },
+                )?,
+            },
+            Ok(WsMessage::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        }
+
+        for reply in rx_output.try_iter() {
+            send_reply(&mut socket, &reply)?;
+        }
+    }
+}
+
+fn send_reply(socket: &mut WebSocket<TcpStream>, reply: &WebSocketReply) -> Result<(), String> {
+    let text = serde_json::to_string(reply).map_err(|e| e.to_string())?;
+    socket
+        .send(WsMessage::Text(text))
+        .map_err(|e| e.to_string())
+}
+
+fn receive_websocket_events(
+    mut commands: Commands,
+    channels: NonSend<WebSocketChannels>,
+    mut clients: ResMut<WebSocketClients>,
+    permissions: Res<WebSocketClientPermissions>,
+    mut requests: ResMut<WebSocketRequests>,
+    mut command_input: EventWriter<CommandBufInput>,
+) {
+    for event in channels.rx_event.try_iter() {
+        match event {
+            WebSocketEvent::Connected {
+                id,
+                peer,
+                tx_output,
+            } => {
+                info!("WebSocket client {} connected", peer);
+                let mut client = commands.spawn((
+                    Name::new(format!("WebSocket client {}", peer)),
+                    WebSocketClient { peer, tx_output },
+                ));
+                if let Some(permissions) = &permissions.0 {
+                    client.insert(permissions.clone());
+                }
+                clients.0.insert(id, client.id());
+            }
+            WebSocketEvent::Request { id, request } => {
+                let Some(&sender) = clients.0.get(&id) else {
+                    continue;
+                };
+                let input = CommandBufInput::new(sender, request.line);
+                requests
+                    .0
+                    .entry(sender)
+                    .or_default()
+                    .insert(input.id, request.id);
+                command_input.send(input);
+            }
+            WebSocketEvent::Disconnected { id } => {
+                if let Some(sender) = clients.0.remove(&id) {
+                    info!("WebSocket client {:?} disconnected", sender);
+                    requests.0.remove(&sender);
+                    commands.entity(sender).despawn();
+                }
+            }
+        }
+    }
+}
+
+fn respond_websockets(
+    mut resps: EventReader<CommandResponse>,
+    mut completed: EventReader<CommandCompleted>,
+    chains: Res<CommandChains>,
+    mut requests: ResMut<WebSocketRequests>,
+    clients: Query<&WebSocketClient>,
+) {
+    for resp in resps.iter() {
+        let Ok(client) = clients.get(resp.target) else {
+            continue;
+        };
+        let invocation = chains.root_id(resp.id);
+        let id = requests
+            .0
+            .get(&resp.target)
+            .and_then(|pending| pending.get(&invocation))
+            .cloned()
+            .unwrap_or_default();
+        // the socket may already have closed if the client disconnected
+        let _ = client.tx_output.send(WebSocketReply::Response {
+            id,
+            invocation: invocation.0,
+            outcome: resp.outcome,
+            message: resp.message.to_string(),
+            spans: WebSocketSpan::from_message(&resp.message),
+        });
+    }
+
+    for event in completed.iter() {
+        let Ok(client) = clients.get(event.target) else {
+            continue;
+        };
+        let Some(id) = requests
+            .0
+            .get_mut(&event.target)
+            .and_then(|pending| pending.remove(&event.id))
+        else {
+            continue;
+        };
+        let _ = client.tx_output.send(WebSocketReply::Completed {
+            id,
+            invocation: event.id.0,
+            outcome: event.outcome,
+        });
+    }
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "tcp")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_commands::inbuilt::InbuiltCommandsPlugin;
+use bevy_commands::tcp::{TcpInputAddr, TcpInputPlugin};
+use bevy_commands::CommandsPlugin;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins).add_plugins((
+        CommandsPlugin,
+        InbuiltCommandsPlugin,
+        TcpInputPlugin::new("127.0.0.1:0", "pw"),
+    ));
+    app.update();
+    app
+}
+
+fn connect(app: &App) -> (TcpStream, Receiver<String>) {
+    let addr = app.world.resource::<TcpInputAddr>().0;
+    let stream = TcpStream::connect(addr).unwrap();
+    let reader = BufReader::new(stream.try_clone().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    (stream, rx)
+}
+
+/// Updates the app until `count` lines are received from the server.
+fn receive(app: &mut App, rx: &Receiver<String>, count: usize) -> Vec<String> {
+    let start = Instant::now();
+    let mut lines = Vec::new();
+    while lines.len() < count {
+        assert!(start.elapsed() < TIMEOUT, "timed out, received {:?}", lines);
+        app.update();
+        lines.extend(rx.try_iter());
+        thread::sleep(Duration::from_millis(5));
+    }
+    lines
+}
+
+#[test]
+fn streams_responses_after_authenticating() {
+    let mut app = app();
+    let (mut stream, rx) = connect(&app);
+
+    writeln!(stream, "pw").unwrap();
+    assert_eq!(receive(&mut app, &rx, 1), ["Authenticated"]);
+
+    writeln!(stream, "echo hi; echo there").unwrap();
+    assert_eq!(receive(&mut app, &rx, 2), ["hi", "there"]);
+}
+
+#[test]
+fn rejects_wrong_password() {
+    let mut app = app();
+    let (mut stream, rx) = connect(&app);
+
+    writeln!(stream, "not pw").unwrap();
+    assert_eq!(receive(&mut app, &rx, 1), ["Authentication failed"]);
+}